@@ -4,7 +4,9 @@
 use chrono::format;
 
 use crate::error::Error;
+use crate::serializer::SerializerRegistry;
 use crate::state::State;
+use crate::thread_safety::MaybeSend;
 use crate::value::ValueInterface;
 use std::collections::HashMap;
 use std::marker::PhantomData;
@@ -20,7 +22,8 @@ use crate::query::Query;
 /// Store uses Key as a key, while Cache uses a Query.
 /// Primary use of Cache is accelerating the evaluation of queries and making short-lived results available via web API.
 /// Binary cache interface is enough to implement the cache web API.
-pub trait BinCache {
+/// With the `thread-safe` feature binary cache is Send, so that environments using it can be shared between threads.
+pub trait BinCache: MaybeSend {
     /// Clean the cache
     /// Empties all the data in the cache
     fn clear(&mut self);
@@ -141,9 +144,12 @@ impl BinCache for MemoryBinCache {
     }
 }
 
+/// Cache of states.
+/// The serializer registry of the environment is passed on every call,
+/// so caches storing serialized values always use the current registry.
 pub trait Cache<V:ValueInterface>:BinCache{
-    fn get(&self, query:&Query)->Result<State<V>,Error>;
-    fn set(&mut self, state:State<V>)->Result<(),Error>;
+    fn get(&self, query:&Query, serializer_registry:&SerializerRegistry<V>)->Result<State<V>,Error>;
+    fn set(&mut self, state:State<V>, serializer_registry:&SerializerRegistry<V>)->Result<(),Error>;
 }
 
 pub struct NoCache<V:ValueInterface>(PhantomData<V>);
@@ -166,16 +172,92 @@ impl <V:ValueInterface> BinCache for NoCache<V>{
 }
 
 impl<V:ValueInterface> Cache<V> for NoCache<V>{
-    fn get(&self, _query:&Query)->Result<State<V>,Error> {
+    fn get(&self, _query:&Query, _serializer_registry:&SerializerRegistry<V>)->Result<State<V>,Error> {
         Err(Error::cache_not_supported())
     }
-    fn set(&mut self, _state:State<V>)->Result<(),Error> {
+    fn set(&mut self, _state:State<V>, _serializer_registry:&SerializerRegistry<V>)->Result<(),Error> {
         Err(Error::cache_not_supported())
     }
 }
 
+/// Cache keeping the states in memory
+/// Values are not serialized, binary access serializes/deserializes on demand.
+pub struct MemoryCache<V:ValueInterface>(HashMap<Query, State<V>>);
+
+impl<V:ValueInterface> MemoryCache<V>{
+    pub fn new()->Self{
+        MemoryCache(HashMap::new())
+    }
+}
+
+impl<V:ValueInterface> BinCache for MemoryCache<V>{
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn get_binary(&self, query: &Query) -> Option<Vec<u8>> {
+        let state = self.0.get(query)?;
+        let extension = state.metadata.extension().unwrap_or(state.data.default_extension().to_string());
+        state.data.as_bytes(&extension).ok()
+    }
+
+    fn get_metadata(&self, query: &Query) -> Option<Arc<Metadata>> {
+        self.0.get(query).map(|state| state.metadata.clone())
+    }
+
+    fn set_binary(&mut self, data: &[u8], metadata: &Metadata) -> Result<(), Error> {
+        let query = metadata.query()?;
+        let type_identifier = metadata.type_identifier()?;
+        let extension = metadata.extension().unwrap_or("b".to_owned());
+        let value = V::deserialize_from_bytes(data, &type_identifier, &extension)?;
+        self.0.insert(query, State::from_value_and_metadata(value, Arc::new(metadata.clone())));
+        Ok(())
+    }
+
+    fn set_metadata(&mut self, metadata: &Metadata) -> Result<(), Error> {
+        let query = metadata.query()?;
+        if let Some(state) = self.0.get_mut(&query) {
+            state.metadata = Arc::new(metadata.clone());
+            Ok(())
+        } else {
+            Err(Error::not_available().with_query(&query))
+        }
+    }
+
+    fn remove(&mut self, query: &Query) -> Result<(), Error> {
+        self.0.remove(query);
+        Ok(())
+    }
+
+    fn contains(&self, query: &Query) -> bool {
+        self.0.contains_key(query)
+    }
+
+    fn keys(&self) -> Vec<Query> {
+        self.0.keys().cloned().collect()
+    }
+}
+
+impl<V:ValueInterface> Cache<V> for MemoryCache<V>{
+    fn get(&self, query:&Query, _serializer_registry:&SerializerRegistry<V>)->Result<State<V>,Error> {
+        self.0.get(query).cloned().ok_or(Error::not_available().with_query(query))
+    }
+    fn set(&mut self, state:State<V>, _serializer_registry:&SerializerRegistry<V>)->Result<(),Error> {
+        let query = state.metadata.query()?;
+        self.0.insert(query, state);
+        Ok(())
+    }
+}
+
+/// Cache storing the states in a binary cache, values are serialized with the [SerializerRegistry] of the environment
 pub struct SerializingCache<V:ValueInterface,BC:BinCache>(BC,PhantomData<V>);
 
+impl<V:ValueInterface, BC:BinCache> SerializingCache<V, BC>{
+    pub fn new(bin_cache:BC)->Self{
+        SerializingCache(bin_cache, PhantomData)
+    }
+}
+
 impl<V:ValueInterface, BC:BinCache> BinCache for SerializingCache<V, BC>{
     fn clear(&mut self) {
         self.0.clear()
@@ -211,24 +293,24 @@ impl<V:ValueInterface, BC:BinCache> BinCache for SerializingCache<V, BC>{
 }
 
 impl<V:ValueInterface, BC:BinCache> Cache<V> for SerializingCache<V, BC>{
-    fn get(&self, query:&Query)->Result<State<V>,Error> {
+    fn get(&self, query:&Query, serializer_registry:&SerializerRegistry<V>)->Result<State<V>,Error> {
         let b = self.get_binary(query).ok_or(Error::not_available().with_query(query))?;
         let metadata = self.get_metadata(query).ok_or(Error::not_available().with_query(query))?;
         let type_identifier = metadata.type_identifier()?;
         let extension = metadata.extension().unwrap_or("b".to_owned()); // TODO: what is the default extension ?
-        let value = V::deserialize_from_bytes(&b, &type_identifier, &extension)?;
+        let value = serializer_registry.deserialize(&b, &type_identifier, &extension)?;
         Ok(State::from_value_and_metadata(value, metadata))
     }
 
-    fn set(&mut self, state:State<V>)->Result<(),Error> {
+    fn set(&mut self, state:State<V>, serializer_registry:&SerializerRegistry<V>)->Result<(),Error> {
         let value = state.data.as_ref();
         if let Some(extension) = state.metadata.extension(){
-            let b = state.data.as_bytes(&extension)?;
+            let b = serializer_registry.serialize(&state.data, &extension)?;
             self.set_binary(&b, &state.metadata)?;
         }
         else{
             let extension = value.default_extension();
-            let b = value.as_bytes(extension.as_ref())?;
+            let b = serializer_registry.serialize(value, extension.as_ref())?;
             let mut metadata = state.metadata.as_ref().clone();
             metadata.set_extension(&extension)?;
             self.set_binary(&b, &metadata)?;
@@ -99,6 +99,10 @@ impl EnumArgument {
         });
         self
     }
+    /// Add an alternative, which is a string value aliased by itself
+    pub fn with_alternative(&mut self, name: &str) -> &mut Self {
+        self.with_value(name, Value::String(name.to_string()))
+    }
     pub fn with_value_type(&mut self, value_type: EnumArgumentType) -> &mut Self {
         self.value_type = value_type;
         self
@@ -181,33 +185,36 @@ pub enum DefaultValue {
 }
 
 impl DefaultValue {
-    fn new() -> Self {
+    pub fn new() -> Self {
         DefaultValue::NoDefault
     }
-    fn null() -> Self {
+    pub fn null() -> Self {
         DefaultValue::Value(Value::Null)
     }
-    fn is_null(&self) -> bool {
+    pub fn is_null(&self) -> bool {
         match self {
             DefaultValue::Value(value) => value.is_null(),
             _ => false,
         }
     }
-    fn from_value(value: Value) -> Self {
+    pub fn from_value(value: Value) -> Self {
         DefaultValue::Value(value)
     }
-    fn from_query(query: Query) -> Self {
+    pub fn from_query(query: Query) -> Self {
         DefaultValue::Query(query)
     }
-    fn from_string(value: &str) -> Self {
+    pub fn from_string(value: &str) -> Self {
         DefaultValue::Value(Value::String(value.to_string()))
     }
-    fn from_integer(value: i64) -> Self {
+    pub fn from_integer(value: i64) -> Self {
         DefaultValue::Value(Value::Number(serde_json::Number::from(value)))
     }
-    fn from_float(value: f64) -> Self {
+    pub fn from_float(value: f64) -> Self {
         DefaultValue::Value(Value::Number(serde_json::Number::from_f64(value).unwrap()))
     }
+    pub fn from_bool(value: bool) -> Self {
+        DefaultValue::Value(Value::Bool(value))
+    }
 }
 
 impl Default for DefaultValue {
@@ -329,6 +336,20 @@ impl ArgumentInfo {
         self.label = label.to_string();
         self
     }
+    pub fn with_default_value(&mut self, default: DefaultValue) -> &mut Self {
+        self.default = default;
+        self
+    }
+    pub fn with_gui_info(&mut self, gui_info: ArgumentGUIInfo) -> &mut Self {
+        self.gui_info = gui_info;
+        self
+    }
+    /// Make the argument an enum argument with the EnumSelector gui
+    pub fn with_enum(&mut self, enum_argument: EnumArgument) -> &mut Self {
+        self.argument_type = ArgumentType::Enum(enum_argument);
+        self.gui_info = ArgumentGUIInfo::EnumSelector;
+        self
+    }
 }
 
 const DEFAULT_REALM: &str = "main";
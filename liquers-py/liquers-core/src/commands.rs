@@ -3,16 +3,22 @@
 
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::future::Future;
 use std::marker::PhantomData;
 
+use async_trait::async_trait;
+
 use nom::Err;
 
 use crate::command_metadata::{self, CommandKey, CommandMetadata, CommandMetadataRegistry};
 use crate::context::{self, Context, EnvRef, Environment};
 use crate::error::{Error, ErrorType};
+use crate::metadata::{Metadata, Status};
+use crate::parse::parse_key;
 use crate::plan::{Parameter, ResolvedParameters};
-use crate::query::Position;
+use crate::query::{Position, Query};
 use crate::state::State;
+use crate::thread_safety::{MaybeSend, MaybeSync};
 use crate::value::ValueInterface;
 
 
@@ -72,6 +78,95 @@ impl CommandArguments{
             self.action_position.clone()
         }
     }
+    /// Completes an error returned by a command with the action position
+    /// and the query of the context (if not specified already)
+    pub fn complete_error<ER: EnvRef<E>, E: Environment>(&self, error: Error, context: &Context<ER, E>) -> Error {
+        self.complete_error_with_query(error, &context.get_query())
+    }
+    /// Completes an error returned by a command with the action position
+    /// and the query (if not specified already)
+    pub fn complete_error_with_query(&self, error: Error, query: &Query) -> Error {
+        let error = if error.position.is_unknown() {
+            error.with_position(&self.action_position)
+        } else {
+            error
+        };
+        if error.query.is_none() && !query.is_empty() {
+            error.with_query(query)
+        } else {
+            error
+        }
+    }
+}
+
+/// Conversion of a command function result into the command result.
+/// This is implemented for the value type itself, for basic types convertible into the value
+/// and for `Result<R, E>`, where `R` is convertible and `E: Into<Error>`.
+/// Thus commands can be fallible.
+/// Value types created by [crate::value_enum] implement it for their extra variant types.
+/// Other types convertible into the value can be returned wrapped in [Ret].
+pub trait IntoCommandResult<V: ValueInterface> {
+    fn into_command_result(self) -> Result<V, Error>;
+}
+
+impl<V: ValueInterface> IntoCommandResult<V> for V {
+    fn into_command_result(self) -> Result<V, Error> {
+        Ok(self)
+    }
+}
+
+impl<V, R, Err> IntoCommandResult<V> for Result<R, Err>
+where
+    V: ValueInterface,
+    R: IntoCommandResult<V>,
+    Err: Into<Error>,
+{
+    fn into_command_result(self) -> Result<V, Error> {
+        match self {
+            Ok(result) => result.into_command_result(),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+macro_rules! into_command_result {
+    ($($t:ty),*) => {
+        $(
+            impl<V: ValueInterface + From<$t>> IntoCommandResult<V> for $t {
+                fn into_command_result(self) -> Result<V, Error> {
+                    Ok(V::from(self))
+                }
+            }
+        )*
+    };
+}
+
+into_command_result!(
+    String,
+    i32,
+    i64,
+    f64,
+    bool,
+    (),
+    Vec<u8>,
+    Vec<crate::value::Value>,
+    std::collections::BTreeMap<String, crate::value::Value>
+);
+
+impl<'a, V: ValueInterface + From<&'a str>> IntoCommandResult<V> for &'a str {
+    fn into_command_result(self) -> Result<V, Error> {
+        Ok(V::from(self))
+    }
+}
+
+/// Wrapper for returning any type convertible into the value type from a command
+#[derive(Debug, Clone)]
+pub struct Ret<T>(pub T);
+
+impl<V: ValueInterface + From<T>, T> IntoCommandResult<V> for Ret<T> {
+    fn into_command_result(self) -> Result<V, Error> {
+        Ok(V::from(self.0))
+    }
 }
 
 /// Command trait
@@ -81,7 +176,8 @@ impl CommandArguments{
 /// - value V encapsulating the main value type
 /// - environment E encapsulating the environment
 /// - EnvRef<E> specifies how the environment is referenced
-pub trait Command<ER: EnvRef<E>, E:Environment, V: ValueInterface> {
+/// With the `thread-safe` feature commands are Send and Sync, so that they can be shared between threads together with the environment.
+pub trait Command<ER: EnvRef<E>, E:Environment, V: ValueInterface>: MaybeSend + MaybeSync {
     fn execute(
         &self,
         state: &State<V>,
@@ -126,7 +222,7 @@ where
     F: Fn() -> R,
 {
     f: F,
-    result: PhantomData<R>,
+    result: PhantomData<fn() -> R>,
 }
 
 impl<R, F> From<F> for Command0<R, F>
@@ -143,10 +239,12 @@ where
 
 impl<F, ER, E, V, R> Command<ER, E, V> for Command0<R, F>
 where
+    F: MaybeSend + MaybeSync,
     F: Fn() -> R,
     R:Clone,
     F:Clone,
-    V: ValueInterface + From<R>,
+    V: ValueInterface,
+    R: IntoCommandResult<V>,
     E:Environment,
     ER:EnvRef<E>,
 {
@@ -157,8 +255,9 @@ where
         context: Context<ER, E>,
     ) -> Result<V, Error> {
         if arguments.has_no_parameters() {
-            let result = (self.f)();
-            Ok(V::from(result))
+            (self.f)()
+                .into_command_result()
+                .map_err(|e| arguments.complete_error(e, &context))
         } else {
             Err(Error::new(
                 ErrorType::TooManyParameters,
@@ -177,9 +276,9 @@ where
     ER:EnvRef<E>,
 {
     f: F,
-    envref: PhantomData<ER>,
-    environment: PhantomData<E>,
-    result: PhantomData<R>,
+    envref: PhantomData<fn() -> ER>,
+    environment: PhantomData<fn() -> E>,
+    result: PhantomData<fn() -> R>,
 }
 
 impl<ER, E, R, F> From<F> for Command0c<ER, E, R, F>
@@ -198,127 +297,397 @@ where
     }
 }
 
-#[derive(Clone)]
-pub struct Command1<S, R, F>
-where
-    F: Fn(S) -> R,
-{
-    f: F,
-    state: PhantomData<S>,
-    result: PhantomData<R>,
-}
-
-impl<S, R, F> From<F> for Command1<S, R, F>
-where
-    F: Fn(S) -> R,
-{
-    fn from(f: F) -> Self {
-        Command1 {
-            f,
-            state: Default::default(),
-            result: Default::default(),
-        }
-    }
-}
-
-impl<F, ER, E, V, R> Command<ER, E, V> for Command1<&State<V>, R, F>
+impl<F, ER, E, V, R> Command<ER, E, V> for Command0c<ER, E, R, F>
 where
-    F: Fn(&State<V>) -> R,
-    R:Clone,
-    F:Clone,
-    V: ValueInterface + From<R>,
+    F: MaybeSend + MaybeSync,
+    F: Fn(Context<ER,E>) -> R,
+    V: ValueInterface,
+    R: IntoCommandResult<V>,
     E:Environment,
     ER:EnvRef<E>,
 {
     fn execute(
         &self,
-        state: &State<V>,
+        _state: &State<V>,
         arguments: &mut CommandArguments,
         context: Context<ER, E>,
     ) -> Result<V, Error> {
-
-        if !arguments.all_parameters_used(){
+        if arguments.has_no_parameters() {
+            let query = context.get_query();
+            (self.f)(context)
+                .into_command_result()
+                .map_err(|e| arguments.complete_error_with_query(e, &query))
+        } else {
             Err(Error::new(
                 ErrorType::TooManyParameters,
-                format!("Too many parameters: {} - no parameters expected, {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                format!("Too many parameters ({}) - none expected", arguments.len()),
             )
-            .with_position(&arguments.parameter_position()))
-        }
-        else{
-            let result = (self.f)(state);
-            Ok(V::from(result))
+            .with_position(&arguments.action_position))
         }
     }
 }
-/*
-impl<F: Fn(&State<V>) -> R + 'static, Injection, V, R> From<F> for Box<dyn Command<Injection, V>>
-where
-R: 'static,
-V: ValueInterface + From<R> + 'static,
-{
-    fn from(f: F) -> Self {
-        Box::new(Command1::from(f))
+
+/// Generates a command wrapper for a function taking the state as the first argument,
+/// followed by typed arguments.
+/// Arguments are obtained via [FromCommandArguments], so injected arguments
+/// (e.g. [Context]) may appear at any position after the state.
+macro_rules! state_command {
+    ($name:ident $(, $t:ident $arg:ident)*) => {
+        #[derive(Clone)]
+        pub struct $name<S, $($t,)* R, F>
+        where
+            F: Fn(S $(, $t)*) -> R,
+        {
+            f: F,
+            state: PhantomData<fn() -> S>,
+            arguments: PhantomData<fn() -> ($($t,)*)>,
+            result: PhantomData<fn() -> R>,
+        }
+
+        impl<S, $($t,)* R, F> From<F> for $name<S, $($t,)* R, F>
+        where
+            F: Fn(S $(, $t)*) -> R,
+        {
+            fn from(f: F) -> Self {
+                $name {
+                    f,
+                    state: Default::default(),
+                    arguments: Default::default(),
+                    result: Default::default(),
+                }
+            }
+        }
+
+        impl<F, ER, E, V, $($t,)* R> Command<ER, E, V> for $name<&State<V>, $($t,)* R, F>
+        where
+            F: MaybeSend + MaybeSync,
+            F: Fn(&State<V> $(, $t)*) -> R,
+            V: ValueInterface,
+            R: IntoCommandResult<V>,
+            $($t: FromCommandArguments<$t, ER, E>,)*
+            E:Environment,
+            ER:EnvRef<E>,
+        {
+            #[allow(unused_variables)]
+            fn execute(
+                &self,
+                state: &State<V>,
+                arguments: &mut CommandArguments,
+                context: Context<ER, E>,
+            ) -> Result<V, Error> {
+                $(let $arg: $t = arguments.get(&context)?;)*
+                if !arguments.all_parameters_used(){
+                    Err(Error::new(
+                        ErrorType::TooManyParameters,
+                        format!("Too many parameters: {}; {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                    )
+                    .with_position(&arguments.parameter_position()))
+                }
+                else{
+                    (self.f)(state $(, $arg)*)
+                        .into_command_result()
+                        .map_err(|e| arguments.complete_error(e, &context))
+                }
+            }
+        }
+    };
+}
+
+/// Generates a command wrapper for a function that does not take the state,
+/// only typed arguments.
+/// Like in state_command, injected arguments may appear at any position.
+macro_rules! stateless_command {
+    ($name:ident $(, $t:ident $arg:ident)+) => {
+        #[derive(Clone)]
+        pub struct $name<$($t,)* R, F>
+        where
+            F: Fn($($t),*) -> R,
+        {
+            f: F,
+            arguments: PhantomData<fn() -> ($($t,)*)>,
+            result: PhantomData<fn() -> R>,
+        }
+
+        impl<$($t,)* R, F> From<F> for $name<$($t,)* R, F>
+        where
+            F: Fn($($t),*) -> R,
+        {
+            fn from(f: F) -> Self {
+                $name {
+                    f,
+                    arguments: Default::default(),
+                    result: Default::default(),
+                }
+            }
+        }
+
+        impl<F, ER, E, V, $($t,)* R> Command<ER, E, V> for $name<$($t,)* R, F>
+        where
+            F: MaybeSend + MaybeSync,
+            F: Fn($($t),*) -> R,
+            V: ValueInterface,
+            R: IntoCommandResult<V>,
+            $($t: FromCommandArguments<$t, ER, E>,)*
+            E:Environment,
+            ER:EnvRef<E>,
+        {
+            fn execute(
+                &self,
+                _state: &State<V>,
+                arguments: &mut CommandArguments,
+                context: Context<ER, E>,
+            ) -> Result<V, Error> {
+                $(let $arg: $t = arguments.get(&context)?;)*
+                if !arguments.all_parameters_used(){
+                    Err(Error::new(
+                        ErrorType::TooManyParameters,
+                        format!("Too many parameters: {}; {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                    )
+                    .with_position(&arguments.parameter_position()))
+                }
+                else{
+                    (self.f)($($arg),*)
+                        .into_command_result()
+                        .map_err(|e| arguments.complete_error(e, &context))
+                }
+            }
+        }
+    };
+}
+
+// CommandN wraps a function with the state and N-1 arguments
+state_command!(Command1);
+state_command!(Command2, T1 a1);
+state_command!(Command3, T1 a1, T2 a2);
+state_command!(Command4, T1 a1, T2 a2, T3 a3);
+state_command!(Command5, T1 a1, T2 a2, T3 a3, T4 a4);
+state_command!(Command6, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5);
+state_command!(Command7, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6);
+state_command!(Command8, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7);
+state_command!(Command9, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7, T8 a8);
+
+// StatelessCommandN wraps a function with N arguments and no state
+stateless_command!(StatelessCommand1, T1 a1);
+stateless_command!(StatelessCommand2, T1 a1, T2 a2);
+stateless_command!(StatelessCommand3, T1 a1, T2 a2, T3 a3);
+stateless_command!(StatelessCommand4, T1 a1, T2 a2, T3 a3, T4 a4);
+stateless_command!(StatelessCommand5, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5);
+stateless_command!(StatelessCommand6, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6);
+stateless_command!(StatelessCommand7, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7);
+stateless_command!(StatelessCommand8, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7, T8 a8);
+
+
+/// Asynchronous variant of the [Command] trait
+/// The state is passed by value (it is cheap to clone), so that the command future does not borrow it.
+/// With the `thread-safe` feature the command futures are Send, so that they can run on multi-threaded executors.
+#[cfg_attr(feature = "thread-safe", async_trait)]
+#[cfg_attr(not(feature = "thread-safe"), async_trait(?Send))]
+pub trait AsyncCommand<ER: EnvRef<E>, E: Environment, V: ValueInterface>: MaybeSend + MaybeSync {
+    async fn execute(
+        &self,
+        state: State<V>,
+        arguments: &mut CommandArguments,
+        context: Context<ER, E>,
+    ) -> Result<V, Error>;
+
+    /// Returns the default metadata of the command
+    fn command_metadata(&self) -> Option<CommandMetadata> {
+        None
     }
 }
-*/
 
-#[derive(Clone)]
-pub struct Command2<S, T, R, F>
+/// AsyncCommand0 wraps an async function without the state and arguments
+pub struct AsyncCommand0<R, Fut, F>
 where
-    F: Fn(S, T) -> R,
+    F: Fn() -> Fut,
+    Fut: Future<Output = R>,
 {
     f: F,
-    state: PhantomData<S>,
-    argument: PhantomData<T>,
-    result: PhantomData<R>,
+    result: PhantomData<fn() -> (R, Fut)>,
 }
 
-impl<S, T, R, F> From<F> for Command2<S, T, R, F>
+impl<R, Fut, F> From<F> for AsyncCommand0<R, Fut, F>
 where
-    F: Fn(S, T) -> R,
+    F: Fn() -> Fut,
+    Fut: Future<Output = R>,
 {
     fn from(f: F) -> Self {
-        Command2 {
+        AsyncCommand0 {
             f,
-            state: Default::default(),
             result: Default::default(),
-            argument: Default::default(),
         }
     }
 }
 
-impl<F, ER, E, V, T, R> Command<ER, E, V> for Command2<&State<V>, T, R, F>
+#[cfg_attr(feature = "thread-safe", async_trait)]
+#[cfg_attr(not(feature = "thread-safe"), async_trait(?Send))]
+impl<F, Fut, ER, E, V, R> AsyncCommand<ER, E, V> for AsyncCommand0<R, Fut, F>
 where
-    F: Fn(&State<V>, T) -> R,
-    R:Clone,
-    F:Clone,
-    T:Clone,
-    V: ValueInterface + From<R>,
-    T: FromCommandArguments<T, ER, E>,
-    E:Environment,
-    ER:EnvRef<E>,
+    F: MaybeSend + MaybeSync,
+    F: Fn() -> Fut,
+    Fut: Future<Output = R> + MaybeSend + 'static,
+    V: ValueInterface + 'static,
+    R: IntoCommandResult<V> + 'static,
+    E: Environment + 'static,
+    ER: EnvRef<E> + MaybeSend + 'static,
 {
-    fn execute(
+    async fn execute(
         &self,
-        state: &State<V>,
+        _state: State<V>,
         arguments: &mut CommandArguments,
         context: Context<ER, E>,
     ) -> Result<V, Error> {
-        let argument: T = arguments.get(&context)?;
-        if !arguments.all_parameters_used(){
+        if arguments.has_no_parameters() {
+            (self.f)()
+                .await
+                .into_command_result()
+                .map_err(|e| arguments.complete_error(e, &context))
+        } else {
             Err(Error::new(
                 ErrorType::TooManyParameters,
-                format!("Too many parameters: {}; {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                format!("Too many parameters ({}) - none expected", arguments.len()),
             )
-            .with_position(&arguments.parameter_position()))
-        }
-        else{
-            let result = (self.f)(state, argument);
-            Ok(V::from(result))
+            .with_position(&arguments.action_position))
         }
     }
 }
 
+macro_rules! async_state_command {
+    ($name:ident $(, $t:ident $arg:ident)*) => {
+        pub struct $name<$($t,)* R, Fut, F> {
+            f: F,
+            arguments: PhantomData<fn() -> ($($t,)*)>,
+            result: PhantomData<fn() -> (R, Fut)>,
+        }
+
+        impl<$($t,)* R, Fut, F> From<F> for $name<$($t,)* R, Fut, F> {
+            fn from(f: F) -> Self {
+                $name {
+                    f,
+                    arguments: Default::default(),
+                    result: Default::default(),
+                }
+            }
+        }
+
+        #[cfg_attr(feature = "thread-safe", async_trait)]
+        #[cfg_attr(not(feature = "thread-safe"), async_trait(?Send))]
+        impl<F, Fut, ER, E, V, $($t,)* R> AsyncCommand<ER, E, V> for $name<$($t,)* R, Fut, F>
+        where
+            F: MaybeSend + MaybeSync,
+            F: Fn(State<V> $(, $t)*) -> Fut,
+            Fut: Future<Output = R> + MaybeSend + 'static,
+            V: ValueInterface + 'static,
+            R: IntoCommandResult<V> + 'static,
+            $($t: FromCommandArguments<$t, ER, E> + 'static,)*
+            E: Environment + 'static,
+            ER: EnvRef<E> + MaybeSend + 'static,
+        {
+            async fn execute(
+                &self,
+                state: State<V>,
+                arguments: &mut CommandArguments,
+                context: Context<ER, E>,
+            ) -> Result<V, Error> {
+                $(let $arg: $t = arguments.get(&context)?;)*
+                if !arguments.all_parameters_used(){
+                    Err(Error::new(
+                        ErrorType::TooManyParameters,
+                        format!("Too many parameters: {}; {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                    )
+                    .with_position(&arguments.parameter_position()))
+                }
+                else{
+                    (self.f)(state $(, $arg)*)
+                        .await
+                        .into_command_result()
+                        .map_err(|e| arguments.complete_error(e, &context))
+                }
+            }
+        }
+    };
+}
+
+// AsyncCommandN wraps an async function with the state and N-1 arguments
+async_state_command!(AsyncCommand1);
+async_state_command!(AsyncCommand2, T1 a1);
+async_state_command!(AsyncCommand3, T1 a1, T2 a2);
+async_state_command!(AsyncCommand4, T1 a1, T2 a2, T3 a3);
+async_state_command!(AsyncCommand5, T1 a1, T2 a2, T3 a3, T4 a4);
+async_state_command!(AsyncCommand6, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5);
+async_state_command!(AsyncCommand7, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6);
+async_state_command!(AsyncCommand8, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7);
+async_state_command!(AsyncCommand9, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7, T8 a8);
+
+/// Generates an async command wrapper for a function that does not take the state,
+/// only typed arguments; asynchronous variant of stateless_command.
+macro_rules! async_stateless_command {
+    ($name:ident $(, $t:ident $arg:ident)+) => {
+        pub struct $name<$($t,)* R, Fut, F> {
+            f: F,
+            arguments: PhantomData<fn() -> ($($t,)*)>,
+            result: PhantomData<fn() -> (R, Fut)>,
+        }
+
+        impl<$($t,)* R, Fut, F> From<F> for $name<$($t,)* R, Fut, F> {
+            fn from(f: F) -> Self {
+                $name {
+                    f,
+                    arguments: Default::default(),
+                    result: Default::default(),
+                }
+            }
+        }
+
+        #[cfg_attr(feature = "thread-safe", async_trait)]
+        #[cfg_attr(not(feature = "thread-safe"), async_trait(?Send))]
+        impl<F, Fut, ER, E, V, $($t,)* R> AsyncCommand<ER, E, V> for $name<$($t,)* R, Fut, F>
+        where
+            F: MaybeSend + MaybeSync,
+            F: Fn($($t),*) -> Fut,
+            Fut: Future<Output = R> + MaybeSend + 'static,
+            V: ValueInterface + 'static,
+            R: IntoCommandResult<V> + 'static,
+            $($t: FromCommandArguments<$t, ER, E> + 'static,)*
+            E: Environment + 'static,
+            ER: EnvRef<E> + MaybeSend + 'static,
+        {
+            async fn execute(
+                &self,
+                _state: State<V>,
+                arguments: &mut CommandArguments,
+                context: Context<ER, E>,
+            ) -> Result<V, Error> {
+                $(let $arg: $t = arguments.get(&context)?;)*
+                if !arguments.all_parameters_used(){
+                    Err(Error::new(
+                        ErrorType::TooManyParameters,
+                        format!("Too many parameters: {}; {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                    )
+                    .with_position(&arguments.parameter_position()))
+                }
+                else{
+                    (self.f)($($arg),*)
+                        .await
+                        .into_command_result()
+                        .map_err(|e| arguments.complete_error(e, &context))
+                }
+            }
+        }
+    };
+}
+
+// AsyncStatelessCommandN wraps an async function with N arguments and no state
+async_stateless_command!(AsyncStatelessCommand1, T1 a1);
+async_stateless_command!(AsyncStatelessCommand2, T1 a1, T2 a2);
+async_stateless_command!(AsyncStatelessCommand3, T1 a1, T2 a2, T3 a3);
+async_stateless_command!(AsyncStatelessCommand4, T1 a1, T2 a2, T3 a3, T4 a4);
+async_stateless_command!(AsyncStatelessCommand5, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5);
+async_stateless_command!(AsyncStatelessCommand6, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6);
+async_stateless_command!(AsyncStatelessCommand7, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7);
+async_stateless_command!(AsyncStatelessCommand8, T1 a1, T2 a2, T3 a3, T4 a4, T5 a5, T6 a6, T7 a7, T8 a8);
+
+
 pub trait FromParameter<T> {
     fn from_parameter(param: &Parameter) -> Result<T, Error>;
 }
@@ -337,6 +706,166 @@ impl FromParameter<String> for String {
     }
 }
 
+macro_rules! from_parameter {
+    ($t:ty, $name:expr, $from_number:expr, $parse:path) => {
+        impl FromParameter<$t> for $t {
+            fn from_parameter(param: &Parameter) -> Result<$t, Error> {
+                let value = match &param.value {
+                    serde_json::Value::Number(n) => $from_number(n),
+                    serde_json::Value::String(s) => $parse(s).ok(),
+                    _ => None,
+                };
+                value.ok_or(Error::conversion_error_at_position(
+                    param.value.clone(),
+                    $name,
+                    &param.position,
+                ))
+            }
+        }
+    };
+}
+
+from_parameter!(i32, "i32", |n: &serde_json::Number| n.as_i64().and_then(|x| i32::try_from(x).ok()), crate::value::parse_i32);
+from_parameter!(i64, "i64", |n: &serde_json::Number| n.as_i64(), crate::value::parse_i64);
+from_parameter!(f64, "f64", |n: &serde_json::Number| n.as_f64(), crate::value::parse_f64);
+
+impl FromParameter<bool> for bool {
+    fn from_parameter(param: &Parameter) -> Result<bool, Error> {
+        let value = match &param.value {
+            serde_json::Value::Bool(b) => Some(*b),
+            serde_json::Value::String(s) => crate::value::parse_bool(s).ok(),
+            _ => None,
+        };
+        value.ok_or(Error::conversion_error_at_position(
+            param.value.clone(),
+            "bool",
+            &param.position,
+        ))
+    }
+}
+
+/// Optional parameter is None if the parameter value is null
+impl<T: FromParameter<T>> FromParameter<Option<T>> for Option<T> {
+    fn from_parameter(param: &Parameter) -> Result<Option<T>, Error> {
+        if param.value.is_null() {
+            Ok(None)
+        } else {
+            T::from_parameter(param).map(Some)
+        }
+    }
+}
+
+/// Vector parameter is created from an array value, each element is converted separately.
+/// A string parameter is parsed as a JSON array if it starts with '[',
+/// otherwise it is split by commas (e.g. `1,2,3`; in a query the comma is encoded as `~U2c~`).
+/// Null and an empty string are converted to an empty vector.
+impl<T: FromParameter<T>> FromParameter<Vec<T>> for Vec<T> {
+    fn from_parameter(param: &Parameter) -> Result<Vec<T>, Error> {
+        let element = |value: serde_json::Value| {
+            T::from_parameter(&Parameter {
+                value,
+                position: param.position.clone(),
+                default: param.default,
+            })
+        };
+        match &param.value {
+            serde_json::Value::Array(values) => values.iter().cloned().map(element).collect(),
+            serde_json::Value::Null => Ok(Vec::new()),
+            serde_json::Value::String(s) if s.trim_start().starts_with('[') => {
+                match serde_json::from_str::<Vec<serde_json::Value>>(s) {
+                    Ok(values) => values.into_iter().map(element).collect(),
+                    Err(e) => Err(Error::conversion_error_with_message(
+                        s,
+                        "array",
+                        &e.to_string(),
+                    )
+                    .with_position(&param.position)),
+                }
+            }
+            serde_json::Value::String(s) if s.is_empty() => Ok(Vec::new()),
+            serde_json::Value::String(s) => s
+                .split(',')
+                .map(|x| element(serde_json::Value::String(x.to_owned())))
+                .collect(),
+            _ => Err(Error::conversion_error_at_position(
+                param.value.clone(),
+                "array",
+                &param.position,
+            )),
+        }
+    }
+}
+
+/// JSON parameter; a string parameter is parsed as JSON if possible, otherwise it is kept as a JSON string
+impl FromParameter<serde_json::Value> for serde_json::Value {
+    fn from_parameter(param: &Parameter) -> Result<serde_json::Value, Error> {
+        match &param.value {
+            serde_json::Value::String(s) => {
+                Ok(serde_json::from_str(s).unwrap_or_else(|_| param.value.clone()))
+            }
+            value => Ok(value.clone()),
+        }
+    }
+}
+
+/// Date in the YYYY-MM-DD format
+impl FromParameter<chrono::NaiveDate> for chrono::NaiveDate {
+    fn from_parameter(param: &Parameter) -> Result<chrono::NaiveDate, Error> {
+        param
+            .value
+            .as_str()
+            .and_then(|s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            .ok_or(Error::conversion_error_at_position(
+                param.value.clone(),
+                "date",
+                &param.position,
+            ))
+    }
+}
+
+/// Date and time in the ISO 8601 format (e.g. 2024-01-31T12:00:00)
+impl FromParameter<chrono::NaiveDateTime> for chrono::NaiveDateTime {
+    fn from_parameter(param: &Parameter) -> Result<chrono::NaiveDateTime, Error> {
+        param
+            .value
+            .as_str()
+            .and_then(|s| s.parse::<chrono::NaiveDateTime>().ok())
+            .ok_or(Error::conversion_error_at_position(
+                param.value.clone(),
+                "datetime",
+                &param.position,
+            ))
+    }
+}
+
+/// UTC timestamp in the RFC 3339 format (e.g. 2024-01-31T12:00:00Z)
+impl FromParameter<chrono::DateTime<chrono::Utc>> for chrono::DateTime<chrono::Utc> {
+    fn from_parameter(param: &Parameter) -> Result<chrono::DateTime<chrono::Utc>, Error> {
+        param
+            .value
+            .as_str()
+            .and_then(|s| s.parse::<chrono::DateTime<chrono::Utc>>().ok())
+            .ok_or(Error::conversion_error_at_position(
+                param.value.clone(),
+                "timestamp",
+                &param.position,
+            ))
+    }
+}
+
+/// Converts a parameter to any type implementing serde Deserialize,
+/// e.g. a user enum. This is used by `#[derive(FromParameter)]` from liquers-macro.
+pub fn deserialize_parameter<T: serde::de::DeserializeOwned>(param: &Parameter) -> Result<T, Error> {
+    serde_json::from_value(param.value.clone()).map_err(|e| {
+        Error::conversion_error_with_message(
+            param.value.clone(),
+            std::any::type_name::<T>(),
+            &e.to_string(),
+        )
+        .with_position(&param.position)
+    })
+}
+
 pub trait FromCommandArguments<T, ER: EnvRef<E>, E:Environment> {
     fn from_arguments(args: &mut CommandArguments, context:&Context<ER, E> ) -> Result<T, Error>;
     fn is_injected() -> bool;
@@ -355,6 +884,17 @@ where
     }
 }
 
+/// Context is injected into the command when requested as an argument.
+/// The injected context shares the metadata with the context of the command execution.
+impl<ER: EnvRef<E>, E:Environment> FromCommandArguments<Context<ER, E>, ER, E> for Context<ER, E> {
+    fn from_arguments(_args: &mut CommandArguments, context:&Context<ER, E>) -> Result<Context<ER, E>, Error> {
+        Ok(context.clone_context())
+    }
+    fn is_injected() -> bool {
+        true
+    }
+}
+
 // TODO: Use CommandKey instead of realm, namespace, command_name
 pub trait CommandExecutor<ER: EnvRef<E>, E:Environment, V: ValueInterface> {
     fn execute<'e>(
@@ -368,6 +908,21 @@ pub trait CommandExecutor<ER: EnvRef<E>, E:Environment, V: ValueInterface> {
     ) -> Result<V, Error>;
 }
 
+/// Asynchronous variant of the [CommandExecutor]
+#[cfg_attr(feature = "thread-safe", async_trait)]
+#[cfg_attr(not(feature = "thread-safe"), async_trait(?Send))]
+pub trait AsyncCommandExecutor<ER: EnvRef<E>, E:Environment, V: ValueInterface> {
+    async fn async_execute(
+        &self,
+        realm: &str,
+        namespace: &str,
+        command_name: &str,
+        state: State<V>,
+        arguments: &mut CommandArguments,
+        context: Context<ER, E>,
+    ) -> Result<V, Error>;
+}
+
 impl<ER: EnvRef<E>, E:Environment, V: ValueInterface> CommandExecutor<ER, E, V> for HashMap<CommandKey, Box<dyn Command<ER, E, V>>> {
     fn execute(
         &self,
@@ -394,6 +949,7 @@ impl<ER: EnvRef<E>, E:Environment, V: ValueInterface> CommandExecutor<ER, E, V>
 
 pub struct CommandRegistry<ER, E, V: ValueInterface> {
     executors: HashMap<CommandKey, Box<dyn Command<ER, E, V>>>,
+    async_executors: HashMap<CommandKey, Box<dyn AsyncCommand<ER, E, V>>>,
     pub command_metadata_registry: CommandMetadataRegistry,
 }
 
@@ -401,6 +957,7 @@ impl<ER: EnvRef<E>, E:Environment, V: ValueInterface> CommandRegistry<ER, E, V>
     pub fn new() -> Self {
         CommandRegistry {
             executors: HashMap::new(),
+            async_executors: HashMap::new(),
             command_metadata_registry: CommandMetadataRegistry::new(),
         }
     }
@@ -438,6 +995,42 @@ impl<ER: EnvRef<E>, E:Environment, V: ValueInterface> CommandRegistry<ER, E, V>
         let command: Box<dyn Command<ER, E, V>> = Box::new(f);
         self.register_boxed_command(key, command)
     }
+    /// Register an async command.
+    /// Async commands are only executed by an asynchronous interpreter, see [AsyncCommandExecutor].
+    pub fn register_boxed_async_command<K>(
+        &mut self,
+        key: K,
+        executor: Box<dyn AsyncCommand<ER, E, V>>,
+    ) -> Result<&mut CommandMetadata, Error>
+    where
+        K: Into<CommandKey>,
+    {
+        let key = key.into();
+        let command_metadata = executor
+            .command_metadata()
+            .map(|cm| {
+                let mut cm = cm.clone();
+                cm.with_realm(&key.realm)
+                    .with_namespace(&key.namespace)
+                    .with_name(&key.name);
+                cm
+            })
+            .unwrap_or((&key).into());
+        self.command_metadata_registry
+            .add_command(&command_metadata);
+
+        self.async_executors.insert(key.clone(), executor);
+        Ok(self.command_metadata_registry.get_mut(key).unwrap())
+    }
+    pub fn register_async_command<K, T>(&mut self, key: K, f: T) -> Result<&mut CommandMetadata, Error>
+    where
+        K: Into<CommandKey>,
+        T: AsyncCommand<ER, E, V> + 'static,
+    {
+        let key = key.into();
+        let command: Box<dyn AsyncCommand<ER, E, V>> = Box::new(f);
+        self.register_boxed_async_command(key, command)
+    }
     /*
     pub fn register<K, T>(&mut self, key: K, f: T) -> Result<&mut CommandMetadata, Error>
     where
@@ -464,6 +1057,13 @@ impl<ER: EnvRef<E>, E:Environment, V: ValueInterface> CommandExecutor<ER, E, V>
         let key = CommandKey::new(realm, namespace, command_name);
         if let Some(command) = self.executors.get(&key) {
             command.execute(state, arguments, context)
+        } else if self.async_executors.contains_key(&key) {
+            Err(Error::async_command_not_supported(
+                realm,
+                namespace,
+                command_name,
+                &arguments.action_position,
+            ))
         } else {
             Err(Error::unknown_command_executor(
                 realm,
@@ -475,6 +1075,89 @@ impl<ER: EnvRef<E>, E:Environment, V: ValueInterface> CommandExecutor<ER, E, V>
     }
 }
 
+/// Async commands are preferred; synchronous commands are executed directly if no async command is registered.
+#[cfg_attr(feature = "thread-safe", async_trait)]
+#[cfg_attr(not(feature = "thread-safe"), async_trait(?Send))]
+impl<ER: EnvRef<E> + MaybeSend + 'static, E: Environment + 'static, V: ValueInterface + 'static> AsyncCommandExecutor<ER, E, V>
+    for CommandRegistry<ER, E, V>
+{
+    async fn async_execute(
+        &self,
+        realm: &str,
+        namespace: &str,
+        command_name: &str,
+        state: State<V>,
+        arguments: &mut CommandArguments,
+        context:Context<ER, E>
+    ) -> Result<V, Error> {
+        let key = CommandKey::new(realm, namespace, command_name);
+        if let Some(command) = self.async_executors.get(&key) {
+            command.execute(state, arguments, context).await
+        } else {
+            self.execute(realm, namespace, command_name, &state, arguments, context)
+        }
+    }
+}
+
+/// Command writing the state to the store under the key given as an argument, e.g. `put-results~Iweekly.csv`.
+/// The data are serialized according to the key extension.
+/// Writing is allowed only to the keys accepted by [Environment::is_writable];
+/// keys with ".", ".." or empty elements are always refused.
+/// The stored metadata have the [Status::SideEffect] status; the state is passed on unchanged.
+pub fn put<ER: EnvRef<E>, E: Environment>(
+    state: &State<E::Value>,
+    context: Context<ER, E>,
+    key: String,
+) -> Result<E::Value, Error> {
+    let key = parse_key(&key)?;
+    if key.has_relative_names() {
+        return Err(Error::permission_denied(format!(
+            "Writing to {} is not allowed, the key must not contain '.', '..' or empty elements",
+            key.encode()
+        )));
+    }
+    if !context.get_environment().is_writable(&key) {
+        return Err(Error::permission_denied(format!(
+            "Writing to {} is not allowed",
+            key.encode()
+        )));
+    }
+    let mut metadata = (*state.metadata).clone();
+    metadata.with_key(key.clone());
+    if let Some(name) = key.filename() {
+        metadata.with_filename(name.name.clone());
+    }
+    let stored = state.with_metadata(metadata);
+    let data = context.get_serializer_registry().serialize_state(&stored)?;
+    let mut metadata = (*stored.metadata).clone();
+    metadata
+        .with_query(context.get_query())
+        .with_data_format(stored.data_format());
+    if let Metadata::MetadataRecord(m) = &mut metadata {
+        m.with_status(Status::SideEffect);
+    }
+    let store = context.get_store();
+    let mut store = store.lock().unwrap();
+    let metadata = store.finalize_metadata(metadata, &key, &data, store.contains(&key));
+    store.set(&key, &data, &metadata)?;
+    context.info(&format!("Stored {}", key.encode()));
+    Ok((*state.data).clone())
+}
+
+/// Register the commands writing to the store (`put`).
+/// These commands are volatile, so that the side effect happens whenever the query is evaluated.
+pub fn register_store_commands<ER, E>(registry: &mut CommandRegistry<ER, E, E::Value>) -> Result<(), Error>
+where
+    ER: EnvRef<E> + 'static,
+    E: Environment + 'static,
+{
+    let cm = registry.register_command("put", Command3::from(put::<ER, E>))?;
+    cm.with_argument(command_metadata::ArgumentInfo::string_argument("key"))
+        .with_doc("Store the data under the key");
+    cm.volatile = true;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use self::context::StatEnvRef;
@@ -573,6 +1256,287 @@ mod tests {
         assert_eq!(s.try_into_string()?, "Hello2");
         Ok(())
     }
+    fn string_parameters(values: &[&str]) -> ResolvedParameters {
+        let mut rp = ResolvedParameters::new();
+        for v in values {
+            rp.parameters.push(Parameter {
+                value: (*v).into(),
+                ..Parameter::default()
+            });
+        }
+        rp
+    }
+    #[test]
+    fn test_higher_arity_commands() -> Result<(), Error> {
+        let injection = Box::leak(Box::new(NoInjection));
+        let context = Context::new(StatEnvRef(injection));
+        let state: State<Value> = State::new().with_string("Hello");
+
+        let c = Command4::from(|s: &State<Value>, a: String, b: String, c: String| -> String {
+            format!("{} {}{}{}", s.data.try_into_string().unwrap(), a, b, c)
+        });
+        let mut ca = CommandArguments::new(string_parameters(&["a", "b", "c"]));
+        let v: Value = c.execute(&state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "Hello abc");
+
+        let c = StatelessCommand3::from(|a: String, b: String, c: String| -> String {
+            format!("{}-{}-{}", a, b, c)
+        });
+        let mut ca = CommandArguments::new(string_parameters(&["x", "y", "z"]));
+        let v: Value = c.execute(&state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "x-y-z");
+
+        let mut ca = CommandArguments::new(string_parameters(&["x", "y"]));
+        let result: Result<Value, Error> = c.execute(&state, &mut ca, context.clone_context());
+        assert_eq!(result.unwrap_err().error_type, ErrorType::ArgumentMissing);
+
+        let c = Command2::from(|_s: &State<Value>, a: String| -> String { a });
+        let mut ca = CommandArguments::new(string_parameters(&["x", "y"]));
+        let result: Result<Value, Error> = c.execute(&state, &mut ca, context.clone_context());
+        assert_eq!(result.unwrap_err().error_type, ErrorType::TooManyParameters);
+        Ok(())
+    }
+    #[test]
+    fn test_context_injection() -> Result<(), Error> {
+        type Ctx = Context<StatEnvRef<NoInjection>, NoInjection>;
+        let injection = Box::leak(Box::new(NoInjection));
+        let context: Ctx = Context::new(StatEnvRef(injection));
+        let state: State<Value> = State::new();
+
+        let c = Command0c::from(|context: Ctx| -> String {
+            context.info("Command0c called");
+            "Hello".into()
+        });
+        let mut ca = CommandArguments::new(ResolvedParameters::new());
+        let v: Value = c.execute(&state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "Hello");
+
+        let c = StatelessCommand3::from(|a: String, context: Ctx, b: String| -> String {
+            context.info("StatelessCommand3 called");
+            format!("{}{}", a, b)
+        });
+        let mut ca = CommandArguments::new(string_parameters(&["x", "y"]));
+        let v: Value = c.execute(&state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "xy");
+
+        let c = Command3::from(|_s: &State<Value>, context: Ctx, a: String| -> String {
+            context.info("Command3 called");
+            a
+        });
+        let mut ca = CommandArguments::new(string_parameters(&["z"]));
+        let v: Value = c.execute(&state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "z");
+
+        assert_eq!(context.get_metadata().log.len(), 3);
+        Ok(())
+    }
+    #[test]
+    fn test_fallible_command() -> Result<(), Error> {
+        type Ctx = Context<StatEnvRef<NoInjection>, NoInjection>;
+        let injection = Box::leak(Box::new(NoInjection));
+        let context: Ctx = Context::new(StatEnvRef(injection));
+        context.set_query(crate::parse::parse_query("fail-x")?);
+        let state: State<Value> = State::new();
+
+        let c = StatelessCommand1::from(|a: String| -> Result<String, Error> {
+            if a == "x" {
+                Err(Error::general_error("Failed".to_string()))
+            } else {
+                Ok(a)
+            }
+        });
+        let mut ca = CommandArguments::new(string_parameters(&["y"]));
+        let v: Value = c.execute(&state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "y");
+
+        let position = Position::new(1, 1, 1);
+        let mut ca = CommandArguments::new(string_parameters(&["x"]));
+        ca.action_position = position.clone();
+        let e = c.execute(&state, &mut ca, context.clone_context()).unwrap_err();
+        assert_eq!(e.error_type, ErrorType::General);
+        assert_eq!(e.position.line, position.line);
+        assert_eq!(e.query, Some("fail-x".to_string()));
+        Ok(())
+    }
+    #[test]
+    fn test_command_result_types() -> Result<(), Error> {
+        type Ctx = Context<StatEnvRef<NoInjection>, NoInjection>;
+        let injection = Box::leak(Box::new(NoInjection));
+        let context: Ctx = Context::new(StatEnvRef(injection));
+        let state: State<Value> = State::new();
+
+        let c = Command0::from(|| vec![1u8, 2]);
+        let v: Value = c.execute(&state, &mut CommandArguments::new(string_parameters(&[])), context.clone_context())?;
+        assert_eq!(v, Value::Bytes(vec![1, 2]));
+        let c = Command0::from(|| -> Result<Vec<Value>, Error> { Ok(vec![Value::I32(1)]) });
+        let v: Value = c.execute(&state, &mut CommandArguments::new(string_parameters(&[])), context.clone_context())?;
+        assert_eq!(v, Value::Array(vec![Value::I32(1)]));
+        let c = Command0::from(|| {
+            std::collections::BTreeMap::from([("a".to_string(), Value::I32(1))])
+        });
+        let v: Value = c.execute(&state, &mut CommandArguments::new(string_parameters(&[])), context.clone_context())?;
+        assert_eq!(v.try_into_object()?.len(), 1);
+        let c = Command0::from(|| Ret(Value::from("x")));
+        let v: Value = c.execute(&state, &mut CommandArguments::new(string_parameters(&[])), context.clone_context())?;
+        assert_eq!(v, Value::from("x"));
+        Ok(())
+    }
+    /// Join the text
+    /// with itself
+    #[liquers_macro::command(
+        namespace = "text",
+        default(separator = "-"),
+        label(separator = "Separator"),
+        gui(separator = TextField(5)),
+        alternatives(separator = ["", "-", "/"])
+    )]
+    fn join_self(state: &State<Value>, separator: String) -> Result<String, Error> {
+        let text = state.data.try_into_string()?;
+        Ok(format!("{}{}{}", text, separator, text))
+    }
+
+    #[liquers_macro::command(name = "hello", volatile)]
+    fn hello_command(
+        context: Context<StatEnvRef<NoInjection>, NoInjection>,
+        who: String,
+    ) -> String {
+        context.info("hello called");
+        format!("Hello {}", who)
+    }
+
+    #[test]
+    fn test_command_macro() -> Result<(), Error> {
+        let mut cr = CommandRegistry::<StatEnvRef<NoInjection>, NoInjection, Value>::new();
+        JoinSelfCommand::register(&mut cr)?;
+        HelloCommandCommand::register(&mut cr)?;
+
+        let cm = cr.command_metadata_registry.get(CommandKey::new("", "text", "join_self")).unwrap();
+        assert_eq!(cm.doc, "Join the text\nwith itself");
+        assert_eq!(cm.state_argument.name, "state");
+        assert_eq!(cm.arguments.len(), 1);
+        assert_eq!(cm.arguments[0].name, "separator");
+        assert_eq!(cm.arguments[0].label, "Separator");
+        assert!(matches!(&cm.arguments[0].default, command_metadata::DefaultValue::Value(v) if v.as_str() == Some("-")));
+        assert!(matches!(cm.arguments[0].gui_info, command_metadata::ArgumentGUIInfo::TextField(5)));
+        if let command_metadata::ArgumentType::Enum(e) = &cm.arguments[0].argument_type {
+            assert_eq!(e.values.len(), 3);
+        } else {
+            panic!("Enum argument expected");
+        }
+        assert!(cm.cache);
+
+        let cm = cr.command_metadata_registry.get("hello").unwrap();
+        assert!(cm.volatile);
+        assert_eq!(cm.arguments.len(), 1);
+        assert_eq!(cm.arguments[0].name, "who");
+
+        let injection = Box::leak(Box::new(NoInjection));
+        let context = Context::new(StatEnvRef(injection));
+        let state = State::new().with_data(Value::from("ab"));
+        let mut ca = CommandArguments::new(string_parameters(&["/"]));
+        let v = cr.execute("", "text", "join_self", &state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "ab/ab");
+
+        let mut ca = CommandArguments::new(string_parameters(&["x"]));
+        let v = cr.execute("", "", "hello", &state, &mut ca, context.clone_context())?;
+        assert_eq!(v.try_into_string()?, "Hello x");
+        assert_eq!(context.get_metadata().log.len(), 1);
+        Ok(())
+    }
+    #[derive(Debug, PartialEq, Deserialize, liquers_macro::FromParameter)]
+    enum Interpolation {
+        #[serde(rename = "linear")]
+        Linear,
+        #[serde(rename = "cubic")]
+        Cubic,
+    }
+
+    fn parameter(value: serde_json::Value) -> Parameter {
+        Parameter {
+            value,
+            position: Position::new(5, 1, 6),
+            default: false,
+        }
+    }
+
+    #[test]
+    fn test_from_parameter() -> Result<(), Error> {
+        use serde_json::json;
+        assert_eq!(i32::from_parameter(&parameter(json!("12")))?, 12);
+        assert_eq!(i32::from_parameter(&parameter(json!(12)))?, 12);
+        assert_eq!(i64::from_parameter(&parameter(json!("-12")))?, -12);
+        assert_eq!(f64::from_parameter(&parameter(json!("1.5")))?, 1.5);
+        assert_eq!(f64::from_parameter(&parameter(json!(2)))?, 2.0);
+        assert!(bool::from_parameter(&parameter(json!("yes")))?);
+        assert!(!bool::from_parameter(&parameter(json!(false)))?);
+        assert_eq!(Option::<i32>::from_parameter(&parameter(json!(null)))?, None);
+        assert_eq!(Option::<i32>::from_parameter(&parameter(json!("3")))?, Some(3));
+        assert_eq!(Vec::<i64>::from_parameter(&parameter(json!(["1", 2])))?, vec![1, 2]);
+        assert_eq!(Vec::<i64>::from_parameter(&parameter(json!("1,2,3")))?, vec![1, 2, 3]);
+        assert_eq!(Vec::<f64>::from_parameter(&parameter(json!("[1, 2.5]")))?, vec![1.0, 2.5]);
+        assert_eq!(Vec::<String>::from_parameter(&parameter(json!("a")))?, vec!["a"]);
+        assert_eq!(Vec::<String>::from_parameter(&parameter(json!("")))?, Vec::<String>::new());
+        assert_eq!(serde_json::Value::from_parameter(&parameter(json!({"a": 1})))?, json!({"a": 1}));
+        assert_eq!(serde_json::Value::from_parameter(&parameter(json!("{\"a\":[1,2]}")))?, json!({"a": [1, 2]}));
+        assert_eq!(serde_json::Value::from_parameter(&parameter(json!("abc")))?, json!("abc"));
+        assert_eq!(
+            chrono::NaiveDate::from_parameter(&parameter(json!("2024-01-31")))?,
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+        assert_eq!(
+            chrono::NaiveDateTime::from_parameter(&parameter(json!("2024-01-31T12:30:00")))?,
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(12, 30, 0).unwrap()
+        );
+        assert_eq!(
+            chrono::DateTime::<chrono::Utc>::from_parameter(&parameter(json!("2024-01-31T12:30:00Z")))?.timestamp(),
+            1706704200
+        );
+        assert_eq!(Interpolation::from_parameter(&parameter(json!("cubic")))?, Interpolation::Cubic);
+
+        for e in [
+            i32::from_parameter(&parameter(json!("x"))).unwrap_err(),
+            i32::from_parameter(&parameter(json!(10000000000i64))).unwrap_err(),
+            bool::from_parameter(&parameter(json!("maybe"))).unwrap_err(),
+            chrono::NaiveDate::from_parameter(&parameter(json!("31.1.2024"))).unwrap_err(),
+            Interpolation::from_parameter(&parameter(json!("nearest"))).unwrap_err(),
+            Vec::<i32>::from_parameter(&parameter(json!("1,x"))).unwrap_err(),
+            Vec::<i32>::from_parameter(&parameter(json!("[1,"))).unwrap_err(),
+        ] {
+            assert_eq!(e.error_type, ErrorType::ConversionError);
+            assert_eq!(e.position.offset, 5);
+        }
+        Ok(())
+    }
+
+    #[liquers_macro::command(default(count = 2, interpolation = "linear"))]
+    fn resample(count: i32, factor: Option<f64>, interpolation: Interpolation) -> String {
+        format!("{} {:?} {:?}", count, factor, interpolation)
+    }
+
+    #[test]
+    fn test_command_macro_types() -> Result<(), Error> {
+        let mut cr = CommandRegistry::<StatEnvRef<NoInjection>, NoInjection, Value>::new();
+        ResampleCommand::register(&mut cr)?;
+        let cm = cr.command_metadata_registry.get("resample").unwrap();
+        assert!(matches!(cm.arguments[0].argument_type, command_metadata::ArgumentType::Integer));
+        assert!(matches!(cm.arguments[1].argument_type, command_metadata::ArgumentType::FloatOption));
+        assert!(cm.arguments[1].default.is_null());
+
+        let injection = Box::leak(Box::new(NoInjection));
+        let context = Context::new(StatEnvRef(injection));
+        let mut ca = CommandArguments::new(ResolvedParameters {
+            parameters: vec![
+                parameter(serde_json::json!("3")),
+                parameter(serde_json::json!(null)),
+                parameter(serde_json::json!("cubic")),
+            ],
+            links: vec![],
+        });
+        let v = cr.execute("", "", "resample", &State::new(), &mut ca, context)?;
+        assert_eq!(v.try_into_string()?, "3 None Cubic");
+        Ok(())
+    }
+
     #[test]
     fn test_command_registry() -> Result<(), Error> {
         let mut cr = CommandRegistry::<StatEnvRef<NoInjection>, NoInjection, Value>::new();
@@ -1,7 +1,12 @@
-use std::{cell::RefCell, marker::PhantomData, rc::Rc, sync::{Arc, Mutex}};
+use std::{marker::PhantomData, rc::Rc, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
 
+use crate::interpreter::{AsyncPlanInterpreter, InterpreterHook, PlanInterpreter};
+use crate::parse::parse_query;
+use crate::plan::PlanBuilder;
+use crate::serializer::SerializerRegistry;
+use crate::thread_safety::{MaybeSend, MaybeSync, SharedCell};
 use crate::{
-    cache::{Cache, NoCache}, command_metadata::CommandMetadataRegistry, commands::{CommandExecutor, CommandRegistry}, error::Error, metadata::{self, MetadataRecord}, query::{Key, Query}, state::State, store::{NoStore, Store}, value::ValueInterface
+    cache::{Cache, NoCache}, command_metadata::CommandMetadataRegistry, commands::{AsyncCommandExecutor, CommandExecutor, CommandRegistry}, error::Error, metadata::{self, Dependency, LogEntry, MetadataRecord, Status}, query::{Key, Query}, state::State, store::{NoStore, Store}, value::ValueInterface
 };
 
 pub trait Environment: Sized{
@@ -12,6 +17,30 @@ pub trait Environment: Sized{
     fn evaluate(&mut self, _query: &Query) -> Result<State<Self::Value>, Error> {
         Err(Error::not_supported("evaluate not implemented".to_string()))
     }
+    /// Maximal number of threads used to evaluate independent dependencies
+    fn max_parallel_evaluations(&self) -> usize {
+        default_max_parallel_evaluations()
+    }
+    /// Thread pool evaluating independent dependencies in parallel, see [ArcEnvRef::evaluate_dependencies].
+    /// The pool is shared by all the evaluations in the environment, so nested evaluations do not add threads.
+    /// Dependencies are evaluated sequentially if there is no pool.
+    #[cfg(feature = "thread-safe")]
+    fn get_thread_pool(&self) -> Option<&rayon::ThreadPool> {
+        None
+    }
+    /// Serializers used to store, cache and return the values
+    fn get_serializer_registry(&self) -> Arc<SerializerRegistry<Self::Value>> {
+        Arc::new(SerializerRegistry::new())
+    }
+    /// Returns true if queries may write to the store key (e.g. with the `put` command).
+    /// Nothing is writable by default.
+    fn is_writable(&self, _key: &Key) -> bool {
+        false
+    }
+    /// Hooks called by the interpreters, in the order of registration
+    fn get_hooks(&self) -> Vec<Arc<dyn InterpreterHook<Self>>> {
+        Vec::new()
+    }
     fn get_command_metadata_registry(&self) -> &CommandMetadataRegistry;
     fn get_mut_command_metadata_registry(&mut self) -> &mut CommandMetadataRegistry;
     fn get_command_executor(&self) -> &Self::CommandExecutor;
@@ -29,8 +58,102 @@ pub trait EnvRef<E: Environment>: Sized {
     fn new_context(&self) -> Context<Self, E> {
         Context::new(self.get_ref())
     }
+    /// Evaluate independent queries (e.g. link parameters of an action).
+    /// Results are in the order of the queries; the first error stops the evaluation.
+    /// Queries are evaluated sequentially by default.
+    fn evaluate_dependencies(
+        &self,
+        queries: &[Query],
+        evaluation_stack: &[Query],
+        cancellation: &Cancellation,
+    ) -> Result<Vec<State<E::Value>>, Error>
+    where
+        E: Environment<EnvironmentReference = Self>,
+    {
+        queries
+            .iter()
+            .map(|query| evaluate_query(self.get_ref(), query, evaluation_stack, cancellation))
+            .collect()
+    }
+}
+
+/// Default of [Environment::max_parallel_evaluations]: the available parallelism of the machine
+pub fn default_max_parallel_evaluations() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// Cancellation flag shared by related evaluations (e.g. the dependencies of an action).
+/// Evaluations check the flag before each step, so running evaluations stop as well.
+/// Cancelling a flag cancels all the flags derived from it by [Cancellation::child], but not its parent.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    parent: Option<Box<Cancellation>>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Flag which is cancelled together with this one
+    pub fn child(&self) -> Self {
+        Cancellation {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            parent: Some(Box::new(self.clone())),
+        }
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self.parent.as_ref().map(|p| p.is_cancelled()).unwrap_or(false)
+    }
+}
+
+/// Evaluate a query in a new interpreter
+/// The evaluation stack contains the queries being evaluated by the callers.
+pub(crate) fn evaluate_query<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>>(
+    envref: ER,
+    query: &Query,
+    evaluation_stack: &[Query],
+    cancellation: &Cancellation,
+) -> Result<State<E::Value>, Error> {
+    let plan = PlanBuilder::new(query.clone(), envref.get().get_command_metadata_registry()).build()?;
+    let mut pi = PlanInterpreter::new(envref);
+    pi.with_evaluation_stack(evaluation_stack.to_vec())
+        .with_cancellation(cancellation.clone())
+        .with_plan(plan)
+        .run()?;
+    pi.get_state()
+        .ok_or(Error::general_error("No state after evaluation".to_string()))
+}
+
+/// Evaluate a query in a new asynchronous interpreter, see [evaluate_query]
+pub(crate) async fn evaluate_query_async<ER, E>(
+    envref: ER,
+    query: &Query,
+    evaluation_stack: &[Query],
+    cancellation: &Cancellation,
+) -> Result<State<E::Value>, Error>
+where
+    ER: EnvRef<E> + MaybeSend + MaybeSync,
+    E: Environment<EnvironmentReference = ER> + MaybeSend + MaybeSync,
+    E::CommandExecutor: AsyncCommandExecutor<ER, E, E::Value>,
+{
+    let plan = PlanBuilder::new(query.clone(), envref.get().get_command_metadata_registry()).build()?;
+    let mut pi = AsyncPlanInterpreter::new(envref);
+    pi.with_evaluation_stack(evaluation_stack.to_vec())
+        .with_cancellation(cancellation.clone())
+        .with_plan(plan);
+    pi.run().await?;
+    pi.get_state()
+        .ok_or(Error::general_error("No state after evaluation".to_string()))
 }
 
+/// Reference to a static environment
 pub struct StatEnvRef<E: Environment + 'static>(pub &'static E);
 
 impl<E: Environment> EnvRef<E> for StatEnvRef<E> {
@@ -42,6 +165,8 @@ impl<E: Environment> EnvRef<E> for StatEnvRef<E> {
     }
 }
 
+/// Reference-counted environment reference for single-threaded use (e.g. in wasm).
+/// Contexts using it are not Send.
 pub struct RcEnvRef<E: Environment>(pub Rc<E>);
 
 impl<E: Environment> EnvRef<E> for RcEnvRef<E> {
@@ -53,34 +178,94 @@ impl<E: Environment> EnvRef<E> for RcEnvRef<E> {
     }
 }
 
+/// Environment reference which can be shared between threads with the `thread-safe` feature.
+/// Contexts using it are Send when the environment is Send + Sync.
+/// Without the feature, it is a cheap reference-counted reference and dependencies are evaluated sequentially.
 pub struct ArcEnvRef<E: Environment>(pub Arc<E>);
 
-impl<E: Environment> EnvRef<E> for ArcEnvRef<E> {
+impl<E: Environment> Clone for ArcEnvRef<E> {
+    fn clone(&self) -> Self {
+        ArcEnvRef(self.0.clone())
+    }
+}
+
+impl<E: Environment + MaybeSend + MaybeSync> EnvRef<E> for ArcEnvRef<E> {
     fn get(&self) -> &E {
         &*self.0
     }
     fn get_ref(&self) -> Self {
         ArcEnvRef(self.0.clone())
     }
+    /// Dependencies are evaluated in parallel by the thread pool of the environment (see [Environment::get_thread_pool]).
+    /// After the first error the other evaluations are cancelled.
+    #[cfg(feature = "thread-safe")]
+    fn evaluate_dependencies(
+        &self,
+        queries: &[Query],
+        evaluation_stack: &[Query],
+        cancellation: &Cancellation,
+    ) -> Result<Vec<State<E::Value>>, Error>
+    where
+        E: Environment<EnvironmentReference = Self>,
+    {
+        use rayon::prelude::*;
+        let pool = match self.get().get_thread_pool() {
+            Some(pool) if queries.len() > 1 => pool,
+            _ => {
+                return queries
+                    .iter()
+                    .map(|query| evaluate_query(self.get_ref(), query, evaluation_stack, cancellation))
+                    .collect()
+            }
+        };
+        let cancellation = cancellation.child();
+        let results: Vec<Result<State<E::Value>, Error>> = pool.install(|| {
+            queries
+                .par_iter()
+                .map(|query| {
+                    let result = evaluate_query(self.get_ref(), query, evaluation_stack, &cancellation);
+                    if result.is_err() {
+                        cancellation.cancel();
+                    }
+                    result
+                })
+                .collect()
+        });
+        // The first failure (in the query order) is reported rather than the resulting cancellations
+        if let Some(Err(e)) = results
+            .iter()
+            .find(|r| matches!(r, Err(e) if e.error_type != crate::error::ErrorType::Cancelled))
+        {
+            return Err(e.clone());
+        }
+        results.into_iter().collect()
+    }
 }
 
 pub struct Context<ER:EnvRef<E>, E: Environment> {
     envref: ER,
-    metadata: Rc<RefCell<MetadataRecord>>,
-    environment: PhantomData<E>
+    metadata: SharedCell<MetadataRecord>,
+    evaluation_stack: Vec<Query>,
+    cancellation: Cancellation,
+    environment: PhantomData<fn() -> E>
 }
 
 impl <ER:EnvRef<E>, E: Environment> Context<ER, E> {
     pub fn new(environment: ER) -> Self {
         Context {
             envref: environment,
-            metadata: Rc::new(RefCell::new(MetadataRecord::new())),
+            metadata: SharedCell::new(MetadataRecord::new()),
+            evaluation_stack: Vec::new(),
+            cancellation: Cancellation::new(),
             environment: PhantomData::default(),
         }
     }
     pub fn get_environment(&self) -> &E {
         self.envref.get()
     }
+    pub fn get_envref(&self) -> ER {
+        self.envref.get_ref()
+    }
     pub fn get_command_metadata_registry(&self) -> &CommandMetadataRegistry {
         self.envref.get().get_command_metadata_registry()
     }
@@ -90,38 +275,170 @@ impl <ER:EnvRef<E>, E: Environment> Context<ER, E> {
     pub fn get_store(&self) -> Arc<Mutex<Box<dyn Store>>> {
         self.envref.get().get_store()
     }
+    pub fn get_serializer_registry(&self) -> Arc<SerializerRegistry<E::Value>> {
+        self.envref.get().get_serializer_registry()
+    }
     pub fn get_metadata(&self) -> MetadataRecord {
-        self.metadata.borrow().clone()
+        self.metadata.lock().clone()
+    }
+    /// Replaces the metadata record of the context
+    pub fn set_metadata(&self, metadata: MetadataRecord) {
+        *self.metadata.lock() = metadata;
     }
     pub fn set_filename(&self, filename: String) {
-        self.metadata.borrow_mut().with_filename(filename);
+        self.metadata.lock().with_filename(filename);
+    }
+    pub fn get_query(&self) -> Query {
+        self.metadata.lock().query.clone()
+    }
+    pub fn set_query(&self, query: Query) {
+        self.metadata.lock().with_query(query);
+    }
+    pub fn set_status(&self, status: Status) {
+        self.metadata.lock().with_status(status);
+    }
+    /// Records a store key or a query the result depends on
+    pub fn add_dependency(&self, dependency: Dependency) {
+        self.metadata.lock().add_dependency(dependency);
+    }
+    /// Records the timing of an evaluated dependency
+    pub fn add_dependency_timing(&self, query: Query, metadata: &metadata::Metadata) {
+        self.metadata.lock().add_dependency_timing(query, metadata);
+    }
+    pub fn start_evaluation(&self) {
+        self.metadata.lock().start_evaluation();
+    }
+    pub fn finish_evaluation(&self) {
+        self.metadata.lock().finish_evaluation();
+    }
+    /// Queries being evaluated, starting with the outermost one.
+    /// The query evaluated in this context is the last one.
+    pub fn get_evaluation_stack(&self) -> Vec<Query> {
+        self.evaluation_stack.clone()
+    }
+    pub fn set_evaluation_stack(&mut self, evaluation_stack: Vec<Query>) {
+        self.evaluation_stack = evaluation_stack;
+    }
+    /// Cancellation flag of the evaluation; sub-queries are evaluated with the same flag
+    pub fn get_cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+    pub fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.cancellation = cancellation;
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+    /// Removes and returns the log entries collected so far
+    pub fn take_log(&self) -> Vec<LogEntry> {
+        std::mem::take(&mut self.metadata.lock().log)
+    }
+    /// Records the error in the metadata and sets the status to Error
+    pub fn set_error(&self, error: &Error) {
+        self.metadata.lock().with_error(error);
     }
     pub fn debug(&self, message:&str){
-        self.metadata.borrow_mut().debug(message);
+        self.metadata.lock().debug(message);
     }
     pub fn info(&self, message:&str){
-        self.metadata.borrow_mut().info(message);
+        self.metadata.lock().info(message);
     }
     pub fn warning(&self, message:&str){
-        self.metadata.borrow_mut().warning(message);
+        self.metadata.lock().warning(message);
     }
     pub fn error(&self, message:&str){
-        self.metadata.borrow_mut().error(message);
+        self.metadata.lock().error(message);
     }
     pub fn clone_context(&self) -> Self {
         Context {
             envref: self.envref.get_ref(),
             metadata: self.metadata.clone(),
+            evaluation_stack: self.evaluation_stack.clone(),
+            cancellation: self.cancellation.clone(),
             environment: PhantomData::default(),
         }
     }
 }
 
+impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> Context<ER, E> {
+    /// Evaluate a query in the environment of the context.
+    /// The query is recorded as a dependency of the result evaluated in this context.
+    /// Query which is already being evaluated results in a cyclic dependency error.
+    /// The query is evaluated synchronously; async commands need [Context::evaluate_async].
+    pub fn evaluate(&self, query: &str) -> Result<State<E::Value>, Error> {
+        self.evaluate_query(&parse_query(query)?)
+    }
+    /// Evaluate a parsed query, see [Context::evaluate]
+    pub fn evaluate_query(&self, query: &Query) -> Result<State<E::Value>, Error> {
+        self.add_dependency(Dependency::Query(query.clone()));
+        let state = evaluate_query(self.get_envref(), query, &self.evaluation_stack, &self.cancellation)?;
+        self.add_dependency_timing(query.clone(), &state.metadata);
+        Ok(state)
+    }
+    /// Evaluate independent queries, possibly in parallel (see [EnvRef::evaluate_dependencies]).
+    /// The queries and their timing are recorded as dependencies.
+    pub fn evaluate_dependencies(&self, queries: &[Query]) -> Result<Vec<State<E::Value>>, Error> {
+        for query in queries {
+            self.add_dependency(Dependency::Query(query.clone()));
+        }
+        let states = self
+            .envref
+            .evaluate_dependencies(queries, &self.evaluation_stack, &self.cancellation)?;
+        for (query, state) in queries.iter().zip(states.iter()) {
+            self.add_dependency_timing(query.clone(), &state.metadata);
+        }
+        Ok(states)
+    }
+}
+
+impl<ER, E> Context<ER, E>
+where
+    ER: EnvRef<E> + MaybeSend + MaybeSync,
+    E: Environment<EnvironmentReference = ER> + MaybeSend + MaybeSync,
+    E::CommandExecutor: AsyncCommandExecutor<ER, E, E::Value>,
+{
+    /// Evaluate a query asynchronously, so that it may use async commands (see [Context::evaluate]).
+    /// Synchronous evaluation of a query with an async command fails with a NotSupported error.
+    pub async fn evaluate_async(&self, query: &str) -> Result<State<E::Value>, Error> {
+        self.evaluate_query_async(&parse_query(query)?).await
+    }
+    /// Evaluate a parsed query asynchronously, see [Context::evaluate_async]
+    pub async fn evaluate_query_async(&self, query: &Query) -> Result<State<E::Value>, Error> {
+        self.add_dependency(Dependency::Query(query.clone()));
+        let state =
+            evaluate_query_async(self.get_envref(), query, &self.evaluation_stack, &self.cancellation).await?;
+        self.add_dependency_timing(query.clone(), &state.metadata);
+        Ok(state)
+    }
+    /// Evaluate independent queries concurrently, see [Context::evaluate_dependencies].
+    /// The remaining evaluations are dropped after the first error.
+    pub async fn evaluate_dependencies_async(&self, queries: &[Query]) -> Result<Vec<State<E::Value>>, Error> {
+        for query in queries {
+            self.add_dependency(Dependency::Query(query.clone()));
+        }
+        let states = futures_util::future::try_join_all(queries.iter().map(|query| {
+            evaluate_query_async(self.get_envref(), query, &self.evaluation_stack, &self.cancellation)
+        }))
+        .await?;
+        for (query, state) in queries.iter().zip(states.iter()) {
+            self.add_dependency_timing(query.clone(), &state.metadata);
+        }
+        Ok(states)
+    }
+}
+
 /// Simple environment with configurable store and cache
 /// CommandRegistry is used as command executor as well as it is providing the command metadata registry.
 pub struct SimpleEnvironment<V: ValueInterface> {
     store: Arc<Mutex<Box<dyn Store>>>,
     cache: Arc<Mutex<Box<dyn Cache<V>>>>,
+    max_parallel_evaluations: Option<usize>,
+    /// Thread pool created on the first use; None if it could not be created
+    #[cfg(feature = "thread-safe")]
+    thread_pool: std::sync::OnceLock<Option<rayon::ThreadPool>>,
+    hooks: Vec<Arc<dyn InterpreterHook<Self>>>,
+    writable_prefixes: Vec<Key>,
+    serializer_registry: Arc<SerializerRegistry<V>>,
     command_registry: CommandRegistry<ArcEnvRef<Self>,Self,V>
 }
 
@@ -131,7 +448,41 @@ impl<V: ValueInterface+'static> SimpleEnvironment<V> {
             store: Arc::new(Mutex::new(Box::new(NoStore))),
             command_registry: CommandRegistry::new(),
             cache: Arc::new(Mutex::new(Box::new(NoCache::new()))),
+            max_parallel_evaluations: None,
+            #[cfg(feature = "thread-safe")]
+            thread_pool: std::sync::OnceLock::new(),
+            hooks: Vec::new(),
+            writable_prefixes: Vec::new(),
+            serializer_registry: Arc::new(SerializerRegistry::new()),
+        }
+    }
+    /// Limit the number of threads evaluating dependencies in parallel
+    /// (the size of the thread pool, which is created on the first use)
+    pub fn with_max_parallel_evaluations(&mut self, max_parallel_evaluations: usize) -> &mut Self {
+        self.max_parallel_evaluations = Some(max_parallel_evaluations);
+        #[cfg(feature = "thread-safe")]
+        {
+            self.thread_pool = std::sync::OnceLock::new();
         }
+        self
+    }
+    /// Register an interpreter hook; hooks are called in the order of registration
+    pub fn with_hook(&mut self, hook: Arc<dyn InterpreterHook<Self>>) -> &mut Self {
+        self.hooks.push(hook);
+        self
+    }
+    pub fn with_serializer_registry(&mut self, serializer_registry: SerializerRegistry<V>) -> &mut Self {
+        self.serializer_registry = Arc::new(serializer_registry);
+        self
+    }
+    /// Serializer registry for registering custom serializers
+    pub fn get_mut_serializer_registry(&mut self) -> &mut SerializerRegistry<V> {
+        Arc::make_mut(&mut self.serializer_registry)
+    }
+    /// Allow queries to write to the store keys starting with the prefix
+    pub fn with_writable_prefix(&mut self, prefix: Key) -> &mut Self {
+        self.writable_prefixes.push(prefix);
+        self
     }
     pub fn with_store(&mut self, store: Box<dyn Store>) -> &mut Self {
         self.store = Arc::new(Mutex::new(store));
@@ -172,5 +523,101 @@ impl<V: ValueInterface> Environment for SimpleEnvironment<V> {
     fn get_cache(&self) -> Arc<Mutex<Box<dyn Cache<Self::Value>>>> {
         self.cache.clone()
     }
+
+    fn get_hooks(&self) -> Vec<Arc<dyn InterpreterHook<Self>>> {
+        self.hooks.clone()
+    }
+
+    fn get_serializer_registry(&self) -> Arc<SerializerRegistry<Self::Value>> {
+        self.serializer_registry.clone()
+    }
+
+    fn is_writable(&self, key: &Key) -> bool {
+        !key.has_relative_names()
+            && self
+                .writable_prefixes
+                .iter()
+                .any(|prefix| key.has_key_prefix(prefix))
+    }
+
+    fn max_parallel_evaluations(&self) -> usize {
+        self.max_parallel_evaluations
+            .unwrap_or_else(default_max_parallel_evaluations)
+    }
+
+    /// If the thread pool can't be created, dependencies are evaluated sequentially.
+    #[cfg(feature = "thread-safe")]
+    fn get_thread_pool(&self) -> Option<&rayon::ThreadPool> {
+        if self.max_parallel_evaluations() <= 1 {
+            return None;
+        }
+        self.thread_pool
+            .get_or_init(|| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(self.max_parallel_evaluations())
+                    .thread_name(|i| format!("liquers-evaluation-{}", i))
+                    .build()
+                    .ok()
+            })
+            .as_ref()
+    }
 }
 
+
+#[cfg(all(test, feature = "thread-safe"))]
+mod tests {
+    use super::*;
+    use crate::commands::Command0;
+    use crate::value::Value;
+
+    type Env = SimpleEnvironment<Value>;
+
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    #[test]
+    fn test_arc_context_is_send() {
+        assert_send::<ArcEnvRef<Env>>();
+        assert_sync::<ArcEnvRef<Env>>();
+        assert_send::<Context<ArcEnvRef<Env>, Env>>();
+        assert_send::<crate::interpreter::PlanInterpreter<ArcEnvRef<Env>, Env>>();
+        assert_send::<State<Value>>();
+    }
+
+    #[test]
+    fn test_evaluate_in_threads() -> Result<(), Error> {
+        let mut env: Env = SimpleEnvironment::new();
+        env.get_mut_command_executor()
+            .register_command("hello", Command0::from(|| "Hello".to_string()))?;
+        let envref = env.to_ref();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let envref = envref.clone();
+                std::thread::spawn(move || crate::interpreter::PlanInterpreter::new(envref).evaluate("hello"))
+            })
+            .collect();
+        for handle in handles {
+            let state = handle.join().unwrap()?;
+            assert_eq!(state.data.try_into_string()?, "Hello");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_dependencies_without_thread_pool() -> Result<(), Error> {
+        let mut env: Env = SimpleEnvironment::new();
+        env.with_max_parallel_evaluations(3);
+        env.get_mut_command_executor()
+            .register_command("hello", Command0::from(|| "Hello".to_string()))?;
+        // Thread pool that failed to be created
+        env.thread_pool.set(None).unwrap();
+        let envref = env.to_ref();
+        assert!(envref.get().get_thread_pool().is_none());
+        let query = crate::parse::parse_query("hello")?;
+        let states =
+            envref.evaluate_dependencies(&[query.clone(), query], &[], &Cancellation::new())?;
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[1].data.try_into_string()?, "Hello");
+        Ok(())
+    }
+}
@@ -21,6 +21,13 @@ pub enum ErrorType {
     UnknownCommand,
     NotSupported,
     NotAvailable,
+    KeyNotFound,
+    KeyNotSupported,
+    KeyReadError,
+    KeyWriteError,
+    CyclicDependency,
+    PermissionDenied,
+    Cancelled,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -81,6 +88,37 @@ impl Error {
             query: None,
         }
     }
+    /// Query depends (directly or indirectly) on itself
+    pub fn cyclic_dependency(query: &crate::query::Query, stack: &[crate::query::Query]) -> Self {
+        Error {
+            error_type: ErrorType::CyclicDependency,
+            message: format!(
+                "Cyclic dependency: {} -> {}",
+                stack.iter().map(|q| q.encode()).join(" -> "),
+                query.encode()
+            ),
+            position: Position::unknown(),
+            query: Some(query.encode()),
+        }
+    }
+    /// Evaluation stopped because a related evaluation failed, see [crate::context::Cancellation]
+    pub fn cancelled() -> Self {
+        Error {
+            error_type: ErrorType::Cancelled,
+            message: "Evaluation cancelled".to_string(),
+            position: Position::unknown(),
+            query: None,
+        }
+    }
+    /// Operation (e.g. writing to a store key) is not allowed by the environment
+    pub fn permission_denied(message: String) -> Self {
+        Error {
+            error_type: ErrorType::PermissionDenied,
+            message: message,
+            position: Position::unknown(),
+            query: None,
+        }
+    }
     pub fn action_not_registered(action: &ActionRequest, namespaces: &Vec<String>) -> Self {
         Error {
             error_type: ErrorType::ActionNotRegistered,
@@ -162,6 +200,18 @@ impl Error {
             query: None,
         }
     }
+    /// Async command executed by a synchronous interpreter
+    pub(crate) fn async_command_not_supported(realm: &str, namespace: &str, command_name: &str, action_position: &Position) -> Error {
+        Error {
+            error_type: ErrorType::NotSupported,
+            message: format!(
+                "Command '{}' (realm:'{}' namespace:'{}') is async and can only be evaluated by an asynchronous interpreter (AsyncPlanInterpreter, Context::evaluate_async)",
+                command_name, realm, namespace
+            ),
+            position: action_position.clone(),
+            query: None,
+        }
+    }
 }
 
 impl fmt::Display for Error {
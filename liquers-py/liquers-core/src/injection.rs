@@ -0,0 +1,222 @@
+//! Standard injectable command arguments.
+//! Types defined here can be used as command arguments;
+//! they are not taken from the query, but provided from the context of the command execution.
+//! Besides these, [Context] itself can be injected to access the log and the metadata.
+
+use std::sync::{Arc, Mutex};
+
+use crate::cache::Cache;
+use crate::commands::{CommandArguments, FromCommandArguments};
+use crate::context::{Context, EnvRef, Environment};
+use crate::error::Error;
+use crate::metadata::Metadata;
+use crate::query::{Key, Position, Query};
+use crate::serializer::SerializerRegistry;
+use crate::state::State;
+use crate::store::Store;
+use crate::value::ValueInterface;
+
+/// Handle to the store of the environment
+/// Store errors are converted to [Error].
+#[derive(Clone)]
+pub struct StoreHandle(pub Arc<Mutex<Box<dyn Store>>>);
+
+impl StoreHandle {
+    /// Get data and metadata
+    pub fn get(&self, key: &Key) -> Result<(Vec<u8>, Metadata), Error> {
+        Ok(self.0.lock().unwrap().get(key)?)
+    }
+    /// Get data as bytes
+    pub fn get_bytes(&self, key: &Key) -> Result<Vec<u8>, Error> {
+        Ok(self.0.lock().unwrap().get_bytes(key)?)
+    }
+    /// Get metadata
+    pub fn get_metadata(&self, key: &Key) -> Result<Metadata, Error> {
+        Ok(self.0.lock().unwrap().get_metadata(key)?)
+    }
+    /// Store data and metadata
+    pub fn set(&self, key: &Key, data: &[u8], metadata: &Metadata) -> Result<(), Error> {
+        Ok(self.0.lock().unwrap().set(key, data, metadata)?)
+    }
+    /// Returns true if store contains the key
+    pub fn contains(&self, key: &Key) -> bool {
+        self.0.lock().unwrap().contains(key)
+    }
+}
+
+impl<ER: EnvRef<E>, E: Environment> FromCommandArguments<StoreHandle, ER, E> for StoreHandle {
+    fn from_arguments(_args: &mut CommandArguments, context: &Context<ER, E>) -> Result<StoreHandle, Error> {
+        Ok(StoreHandle(context.get_store()))
+    }
+    fn is_injected() -> bool {
+        true
+    }
+}
+
+/// Handle to the cache of the environment together with the serializer registry of the environment
+pub struct CacheHandle<V: ValueInterface>(
+    pub Arc<Mutex<Box<dyn Cache<V>>>>,
+    pub Arc<SerializerRegistry<V>>,
+);
+
+impl<V: ValueInterface> CacheHandle<V> {
+    pub fn get(&self, query: &Query) -> Result<State<V>, Error> {
+        self.0.lock().unwrap().get(query, &self.1)
+    }
+    pub fn set(&self, state: State<V>) -> Result<(), Error> {
+        self.0.lock().unwrap().set(state, &self.1)
+    }
+    pub fn contains(&self, query: &Query) -> bool {
+        self.0.lock().unwrap().contains(query)
+    }
+}
+
+impl<ER: EnvRef<E>, E: Environment> FromCommandArguments<CacheHandle<E::Value>, ER, E>
+    for CacheHandle<E::Value>
+{
+    fn from_arguments(
+        _args: &mut CommandArguments,
+        context: &Context<ER, E>,
+    ) -> Result<CacheHandle<E::Value>, Error> {
+        Ok(CacheHandle(
+            context.get_environment().get_cache(),
+            context.get_serializer_registry(),
+        ))
+    }
+    fn is_injected() -> bool {
+        true
+    }
+}
+
+/// Query being evaluated
+#[derive(Debug, Clone)]
+pub struct CurrentQuery(pub Query);
+
+impl<ER: EnvRef<E>, E: Environment> FromCommandArguments<CurrentQuery, ER, E> for CurrentQuery {
+    fn from_arguments(_args: &mut CommandArguments, context: &Context<ER, E>) -> Result<CurrentQuery, Error> {
+        Ok(CurrentQuery(context.get_query()))
+    }
+    fn is_injected() -> bool {
+        true
+    }
+}
+
+/// Position of the action in the query
+#[derive(Debug, Clone)]
+pub struct ActionPosition(pub Position);
+
+impl<ER: EnvRef<E>, E: Environment> FromCommandArguments<ActionPosition, ER, E> for ActionPosition {
+    fn from_arguments(args: &mut CommandArguments, _context: &Context<ER, E>) -> Result<ActionPosition, Error> {
+        Ok(ActionPosition(args.action_position.clone()))
+    }
+    fn is_injected() -> bool {
+        true
+    }
+}
+
+/// Evaluates sub-queries in the environment of the command
+pub struct QueryEvaluator<ER: EnvRef<E>, E: Environment> {
+    context: Context<ER, E>,
+}
+
+impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> QueryEvaluator<ER, E> {
+    /// Evaluate the query and return the resulting state
+    pub fn evaluate(&self, query: &str) -> Result<State<E::Value>, Error> {
+        self.context.evaluate(query)
+    }
+}
+
+impl<ER: EnvRef<E>, E: Environment> FromCommandArguments<QueryEvaluator<ER, E>, ER, E>
+    for QueryEvaluator<ER, E>
+{
+    fn from_arguments(
+        _args: &mut CommandArguments,
+        context: &Context<ER, E>,
+    ) -> Result<QueryEvaluator<ER, E>, Error> {
+        Ok(QueryEvaluator {
+            context: context.clone_context(),
+        })
+    }
+    fn is_injected() -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_metadata::ArgumentInfo;
+    use crate::commands::{Command0, Command3, StatelessCommand1, StatelessCommand2};
+    use crate::context::{ArcEnvRef, SimpleEnvironment};
+    use crate::interpreter::PlanInterpreter;
+    use crate::parse::parse_key;
+    use crate::store::MemoryStore;
+    use crate::value::{Value, ValueInterface};
+
+    type Env = SimpleEnvironment<Value>;
+
+    #[test]
+    fn test_store_injection() -> Result<(), Error> {
+        let mut env: Env = SimpleEnvironment::new();
+        env.with_store(Box::new(MemoryStore::new(&Key::new())));
+        env.get_store()
+            .lock()
+            .unwrap()
+            .set(&parse_key("hello.txt")?, b"Hello", &Metadata::new())?;
+        env.get_mut_command_executor().register_command(
+            "read",
+            StatelessCommand2::from(|store: StoreHandle, key: String| -> Result<String, Error> {
+                let bytes = store.get_bytes(&parse_key(&key)?)?;
+                Ok(String::from_utf8_lossy(&bytes).to_string())
+            }),
+        )?
+        .with_argument(ArgumentInfo::string_argument("key"));
+
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("read-hello.txt")?;
+        assert_eq!(state.data.try_into_string()?, "Hello");
+
+        let e = pi.evaluate("read-missing.txt").unwrap_err();
+        assert_eq!(e.error_type, crate::error::ErrorType::KeyNotFound);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_and_position_injection() -> Result<(), Error> {
+        let mut env: Env = SimpleEnvironment::new();
+        env.get_mut_command_executor().register_command(
+            "where",
+            Command3::from(
+                |_state: &State<Value>, query: CurrentQuery, position: ActionPosition| {
+                    format!("{} {}", query.0.encode(), position.0.offset)
+                },
+            ),
+        )?;
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("where")?;
+        assert_eq!(state.data.try_into_string()?, "where 0");
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_evaluator_injection() -> Result<(), Error> {
+        let mut env: Env = SimpleEnvironment::new();
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_command("hello", Command0::from(|| "Hello".to_string()))?;
+            cr.register_command(
+                "sub",
+                StatelessCommand1::from(
+                    |evaluator: QueryEvaluator<ArcEnvRef<Env>, Env>| -> Result<String, Error> {
+                        let state = evaluator.evaluate("hello")?;
+                        Ok(format!("{} {}", state.data.try_into_string()?, "again"))
+                    },
+                ),
+            )?;
+        }
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("sub")?;
+        assert_eq!(state.data.try_into_string()?, "Hello again");
+        Ok(())
+    }
+}
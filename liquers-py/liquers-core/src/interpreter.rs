@@ -1,17 +1,112 @@
-use crate::commands::{CommandArguments, CommandExecutor};
-use crate::context::{Context, EnvRef, Environment};
-use crate::error::Error;
-use crate::metadata::MetadataRecord;
+use std::sync::{Arc, Mutex};
+
+use crate::cache::Cache;
+use crate::commands::{AsyncCommandExecutor, CommandArguments, CommandExecutor};
+use crate::context::{Cancellation, Context, EnvRef, Environment};
+use crate::error::{Error, ErrorType};
+use crate::metadata::{Dependency, LogEntry, Metadata, MetadataRecord, Status};
 use crate::parse::parse_query;
-use crate::plan::{Plan, PlanBuilder, Step};
+use crate::plan::{Plan, PlanBuilder, ResolvedParameters, Step};
+use crate::query::{Key, Query};
+use crate::serializer::SerializerRegistry;
 use crate::state::State;
+use crate::store::StoreError;
+use crate::thread_safety::{BoxFuture, MaybeSend, MaybeSync};
 use crate::value::ValueInterface;
 
+/// Callbacks of the interpreters, e.g. for auditing, metrics or policies.
+/// Hooks are registered on the [Environment] (see [Environment::get_hooks]).
+/// Returning an error from `before_plan`, `before_step` or `after_step` vetoes the evaluation;
+/// the error becomes the result of the evaluation.
+/// Steps taken from the cache are not evaluated, so the step callbacks are not called for them;
+/// policies which must cover the whole query should be checked in `before_plan`.
+pub trait InterpreterHook<E: Environment>: MaybeSend + MaybeSync {
+    /// Called before the plan is evaluated
+    fn before_plan(&self, _plan: &Plan, _context: &Context<E::EnvironmentReference, E>) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Called before a step is executed
+    fn before_step(
+        &self,
+        _step: &Step,
+        _input_state: &State<E::Value>,
+        _context: &Context<E::EnvironmentReference, E>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Called after a step has been executed successfully
+    fn after_step(
+        &self,
+        _step: &Step,
+        _input_state: &State<E::Value>,
+        _output_state: &State<E::Value>,
+        _context: &Context<E::EnvironmentReference, E>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+    /// Called when a step fails or is vetoed
+    fn on_error(&self, _step: &Step, _error: &Error, _context: &Context<E::EnvironmentReference, E>) {}
+}
+
+/// Hooks are composed by calling them in order; the first veto stops the evaluation.
+impl<E: Environment> InterpreterHook<E> for Vec<Arc<dyn InterpreterHook<E>>> {
+    fn before_plan(&self, plan: &Plan, context: &Context<E::EnvironmentReference, E>) -> Result<(), Error> {
+        self.iter().try_for_each(|hook| hook.before_plan(plan, context))
+    }
+    fn before_step(
+        &self,
+        step: &Step,
+        input_state: &State<E::Value>,
+        context: &Context<E::EnvironmentReference, E>,
+    ) -> Result<(), Error> {
+        self.iter()
+            .try_for_each(|hook| hook.before_step(step, input_state, context))
+    }
+    fn after_step(
+        &self,
+        step: &Step,
+        input_state: &State<E::Value>,
+        output_state: &State<E::Value>,
+        context: &Context<E::EnvironmentReference, E>,
+    ) -> Result<(), Error> {
+        self.iter()
+            .try_for_each(|hook| hook.after_step(step, input_state, output_state, context))
+    }
+    fn on_error(&self, step: &Step, error: &Error, context: &Context<E::EnvironmentReference, E>) {
+        for hook in self.iter() {
+            hook.on_error(step, error, context);
+        }
+    }
+}
+
+/// Point where the step-by-step evaluation stops (see [PlanInterpreter::continue_to_breakpoint])
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    /// Stop before the step with the given index
+    Step(usize),
+    /// Stop before any action with the given name
+    Action(String),
+}
+
+impl Breakpoint {
+    pub fn matches(&self, step_number: usize, step: &Step) -> bool {
+        match (self, step) {
+            (Breakpoint::Step(n), _) => *n == step_number,
+            (Breakpoint::Action(name), Step::Action { action_name, .. }) => name == action_name,
+            _ => false,
+        }
+    }
+}
+
 pub struct PlanInterpreter<ER: EnvRef<E>, E: Environment> {
     plan: Option<Plan>,
     environment: ER,
     step_number: usize,
     state: Option<State<E::Value>>,
+    evaluation_stack: Vec<Query>,
+    cancellation: Cancellation,
+    breakpoints: Vec<Breakpoint>,
+    history: Vec<State<E::Value>>,
 }
 
 impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<ER, E> {
@@ -21,12 +116,31 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
             environment,
             step_number: 0,
             state: None,
+            evaluation_stack: Vec::new(),
+            cancellation: Cancellation::new(),
+            breakpoints: Vec::new(),
+            history: Vec::new(),
         }
     }
+    /// Set the queries being evaluated by the callers (used to detect cyclic dependencies)
+    pub fn with_evaluation_stack(&mut self, evaluation_stack: Vec<Query>) -> &mut Self {
+        self.evaluation_stack = evaluation_stack;
+        self
+    }
+    /// Evaluation stops before the next step when the flag is cancelled
+    pub fn with_cancellation(&mut self, cancellation: Cancellation) -> &mut Self {
+        self.cancellation = cancellation;
+        self
+    }
+    /// Resulting state of the last run
+    pub fn get_state(&self) -> Option<State<E::Value>> {
+        self.state.clone()
+    }
     pub fn with_plan(&mut self, plan: Plan) -> &mut Self {
         println!("with plan {:?}", plan);
         self.plan = Some(plan);
         self.step_number = 0;
+        self.history.clear();
         self
     }
 
@@ -42,19 +156,35 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
         let plan = pb.build()?;
         Ok(self.with_plan(plan))
     }
+    /// Run the plan
+    /// Evaluation starts from the longest prefix of the query available in the cache.
+    /// Results of cacheable actions are stored in the cache.
     pub fn run(&mut self) -> Result<(), Error> {
-        let mut context = self.environment.new_context();
-        if self.plan.is_none() {
-            return Err(Error::general_error("No plan".to_string()));
-        }
-        for i in 0..self.len() {
-            let input_state = self.state.take().unwrap_or(self.initial_state());
-            let step = self.get_step(i)?;
-            let output_state = self.do_step(&step, input_state, context.clone_context())?;
-            self.state = Some(output_state);
+        let mut run = PlanRun::new(
+            self.environment.get_ref(),
+            self.plan.as_ref(),
+            &self.evaluation_stack,
+            &self.cancellation,
+        )?;
+        let result = self.run_steps(&mut run);
+        self.state = run.into_state();
+        result
+    }
+    fn run_steps(&self, run: &mut PlanRun<ER, E>) -> Result<(), Error> {
+        while let Some((step, input_state)) = run.next_step()? {
+            let result = self.do_step(&step, input_state.clone(), run.context.clone_context());
+            run.complete_step(&step, &input_state, result)?;
         }
         Ok(())
     }
+    /// Evaluate the query and return the resulting state
+    pub fn evaluate(&mut self, query: &str) -> Result<State<<E as Environment>::Value>, Error> {
+        self.with_query(query)?;
+        self.run()?;
+        self.state
+            .clone()
+            .ok_or(Error::general_error("No state after evaluation".to_string()))
+    }
     pub fn initial_state(&self) -> State<<E as Environment>::Value> {
         State::new()
     }
@@ -78,6 +208,177 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
         }
         Err(Error::general_error("No plan".to_string()))
     }
+    /// Add a breakpoint for the step-by-step evaluation
+    pub fn with_breakpoint(&mut self, breakpoint: Breakpoint) -> &mut Self {
+        self.breakpoints.push(breakpoint);
+        self
+    }
+    pub fn clear_breakpoints(&mut self) -> &mut Self {
+        self.breakpoints.clear();
+        self
+    }
+    /// Index of the next step to be executed by [PlanInterpreter::step]
+    pub fn step_number(&self) -> usize {
+        self.step_number
+    }
+    /// True if all the steps of the plan have been executed by [PlanInterpreter::step]
+    pub fn is_finished(&self) -> bool {
+        self.step_number >= self.len()
+    }
+    /// State after the given number of executed steps (0 is the initial state).
+    /// Only states computed by [PlanInterpreter::step] are available.
+    pub fn get_intermediate_state(&self, steps: usize) -> Option<State<E::Value>> {
+        self.history.get(steps).cloned()
+    }
+    /// Execute the next step of the plan and return the resulting state.
+    /// Unlike [PlanInterpreter::run], the cache is not used, so that all the intermediate states are computed.
+    /// The metadata of each intermediate state contains the log and dependencies collected so far.
+    /// A failed step is not counted as executed, so it can be repeated (e.g. after [PlanInterpreter::set_parameter]).
+    pub fn step(&mut self) -> Result<State<E::Value>, Error> {
+        let step = self.get_step(self.step_number)?.clone();
+        let hooks = self.environment.get().get_hooks();
+        if self.history.is_empty() {
+            let initial_state = self.debug_initial_state()?;
+            if let Some(plan) = &self.plan {
+                hooks.before_plan(plan, &self.debug_context(&initial_state))?;
+            }
+            self.history.push(initial_state);
+        }
+        let input_state = self.history[self.step_number].clone();
+        let context = self.debug_context(&input_state);
+        let mut log = context.take_log();
+        let result = match hooks.before_step(&step, &input_state, &context) {
+            Ok(()) => self.do_step(&step, input_state.clone(), context.clone_context()),
+            Err(e) => Err(e),
+        };
+        let result = result.and_then(|output_state| {
+            collect_step_log(&context, &step, &mut log);
+            if self.step_number + 1 == self.len() {
+                context.finish_evaluation();
+            }
+            let output_state =
+                output_state.with_metadata(result_metadata(&context, &log, &output_state));
+            hooks.after_step(&step, &input_state, &output_state, &context)?;
+            Ok(output_state)
+        });
+        match result {
+            Ok(output_state) => {
+                self.step_number += 1;
+                self.history.truncate(self.step_number);
+                self.history.push(output_state.clone());
+                self.state = Some(output_state.clone());
+                Ok(output_state)
+            }
+            Err(e) => {
+                let query = context.get_query();
+                let e = if e.query.is_none() && !query.is_empty() {
+                    e.with_query(&query)
+                } else {
+                    e
+                };
+                hooks.on_error(&step, &e, &context);
+                Err(e)
+            }
+        }
+    }
+    /// Execute steps until a breakpoint or the end of the plan is reached.
+    /// At least one step is executed; evaluation stops before the step matching a breakpoint.
+    /// Returns the index of the step where the evaluation stopped or None if the plan is finished.
+    pub fn continue_to_breakpoint(&mut self) -> Result<Option<usize>, Error> {
+        let mut executed = false;
+        while !self.is_finished() {
+            if executed {
+                let step = self.get_step(self.step_number)?;
+                if self.breakpoints.iter().any(|b| b.matches(self.step_number, step)) {
+                    return Ok(Some(self.step_number));
+                }
+            }
+            self.step()?;
+            executed = true;
+        }
+        Ok(None)
+    }
+    /// Return to the state after the given number of steps, so that the evaluation can be replayed from there.
+    pub fn rewind(&mut self, steps: usize) -> Result<(), Error> {
+        let executed = self.history.len().saturating_sub(1);
+        if steps > executed {
+            return Err(Error::general_error(format!(
+                "Can't rewind to step {}, only {} steps were executed",
+                steps, executed
+            )));
+        }
+        self.history.truncate(steps + 1);
+        self.step_number = steps;
+        self.state = self.history.last().cloned();
+        Ok(())
+    }
+    /// Change the value of an action parameter and rewind to the action, so that it is replayed with the new value
+    pub fn set_parameter(
+        &mut self,
+        step_number: usize,
+        parameter_index: usize,
+        value: serde_json::Value,
+    ) -> Result<(), Error> {
+        let step = self
+            .plan
+            .as_mut()
+            .and_then(|plan| plan.steps.get_mut(step_number));
+        if let Some(Step::Action {
+            action_name,
+            parameters,
+            ..
+        }) = step
+        {
+            if let Some(parameter) = parameters.parameters.get_mut(parameter_index) {
+                parameter.value = value;
+                parameters.links.retain(|(i, _)| *i != parameter_index);
+            } else {
+                return Err(Error::general_error(format!(
+                    "Action {} in step {} has no parameter {}",
+                    action_name, step_number, parameter_index
+                )));
+            }
+        } else {
+            return Err(Error::general_error(format!(
+                "Step {} is not an action",
+                step_number
+            )));
+        }
+        if step_number < self.step_number {
+            self.rewind(step_number)?;
+        }
+        Ok(())
+    }
+    fn debug_initial_state(&self) -> Result<State<E::Value>, Error> {
+        let query = self
+            .plan
+            .as_ref()
+            .map(|plan| plan.query.clone())
+            .ok_or(Error::general_error("No plan".to_string()))?;
+        if !query.is_empty() && self.evaluation_stack.contains(&query) {
+            return Err(Error::cyclic_dependency(&query, &self.evaluation_stack));
+        }
+        let mut metadata = MetadataRecord::new();
+        metadata.with_query(query);
+        metadata.start_evaluation();
+        Ok(self
+            .initial_state()
+            .with_metadata(Metadata::MetadataRecord(metadata)))
+    }
+    /// Context for a debugging step, continuing the evaluation record of the input state
+    fn debug_context(&self, input_state: &State<E::Value>) -> Context<ER, E> {
+        let mut context = self.environment.new_context();
+        if let Metadata::MetadataRecord(m) = &*input_state.metadata {
+            context.set_metadata(m.clone());
+        }
+        let query = context.get_query();
+        if !query.is_empty() {
+            let mut evaluation_stack = self.evaluation_stack.clone();
+            evaluation_stack.push(query);
+            context.set_evaluation_stack(evaluation_stack);
+        }
+        context
+    }
     pub fn do_step(
         &self,
         step: &Step,
@@ -85,20 +386,47 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
         context: Context<ER, E>,
     ) -> Result<State<<E as Environment>::Value>, Error> {
         match step {
-            crate::plan::Step::GetResource(key) => {
+            crate::plan::Step::GetResource(key) | crate::plan::Step::GetNamedResource(key) => {
+                let is_dir = self.environment.get_store().lock().unwrap().is_dir(key);
+                if is_dir {
+                    context.add_dependency(Dependency::Key(key.clone()));
+                    return get_directory_listing(key, &context);
+                }
+                let resource = get_resource(key, &context)?;
+                return resource_state(key, resource, &context);
+            }
+            crate::plan::Step::GetResourceDirectory(key) => {
+                context.add_dependency(Dependency::Key(key.clone()));
+                return get_directory_listing(key, &context);
+            }
+            crate::plan::Step::GetResourceBinary(key) => {
+                let resource = get_resource(key, &context)?;
+                return Ok(binary_resource_state(key, resource, &context));
+            }
+            crate::plan::Step::GetResourceMetadata(key)
+            | crate::plan::Step::GetNamedResourceMetadata(key) => {
                 let store = self.environment.get_store();
-                let (data, metadata) = store
+                let metadata = store
                     .lock()
                     .unwrap()
-                    .get(&key)
-                    .map_err(|e| Error::general_error(format!("Store error: {}", e)))?; // TODO: use store error type - convert to Error
-                let value = <<E as Environment>::Value as ValueInterface>::from_bytes(data);
-                return Ok(State::new().with_data(value).with_metadata(metadata));
-            }
-            crate::plan::Step::GetResourceMetadata(_) => todo!(),
-            crate::plan::Step::GetNamedResource(_) => todo!(),
-            crate::plan::Step::GetNamedResourceMetadata(_) => todo!(),
-            crate::plan::Step::Evaluate(_) => todo!(),
+                    .get_metadata(&key)
+                    .map_err(Error::from)?;
+                context.add_dependency(Dependency::Key(key.clone()));
+                let json = metadata.to_json_value().map_err(|e| {
+                    Error::general_error(format!("Metadata serialization error: {}", e))
+                })?;
+                let value = <<E as Environment>::Value as ValueInterface>::try_from_json_value(&json)?;
+                let mut metadata = MetadataRecord::new();
+                metadata
+                    .with_key(key.clone())
+                    .with_media_type("application/json".to_string());
+                return Ok(State::new()
+                    .with_data(value)
+                    .with_metadata(Metadata::MetadataRecord(metadata)));
+            }
+            crate::plan::Step::Evaluate(query) => {
+                return context.evaluate_query(query);
+            }
             crate::plan::Step::Action {
                 realm,
                 ns,
@@ -106,7 +434,7 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
                 position,
                 parameters,
             } => {
-                let mut arguments = CommandArguments::new(parameters.clone());
+                let mut arguments = CommandArguments::new(resolve_links(parameters, &context)?);
                 arguments.action_position = position.clone();
 
                 let ce = self.environment.get().get_command_executor();
@@ -118,14 +446,20 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
                     &mut arguments,
                     context.clone_context(),
                 )?;
-                let state = State::new()
-                    .with_data(result)
-                    .with_metadata(context.get_metadata().into());
-                /// TODO - reset metadata ?
-                return Ok(state);
+                // Metadata of the result is assembled by the interpreter from the context
+                return Ok(State::new().with_data(result));
             }
             crate::plan::Step::Filename(name) => {
                 context.set_filename(name.name.clone());
+                let mut metadata = (*input_state.metadata).clone();
+                metadata.with_filename(name.name.clone());
+                let state = input_state.with_metadata(metadata);
+                // Fail early if the value can't be serialized in the requested format
+                let format = state.data_format();
+                context.get_serializer_registry().check_state_format(&state)?;
+                let mut metadata = (*state.metadata).clone();
+                metadata.with_data_format(format);
+                return Ok(state.with_metadata(metadata));
             }
             crate::plan::Step::Info(m) => {
                 context.info(&m);
@@ -136,12 +470,682 @@ impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanInterpreter<E
             crate::plan::Step::Error(m) => {
                 context.error(&m);
             }
-            crate::plan::Step::Plan(_) => todo!(),
+            crate::plan::Step::Plan(plan) => {
+                let mut state = input_state;
+                for step in plan.steps.iter() {
+                    state = self.do_step(step, state, context.clone_context())?;
+                }
+                return Ok(state);
+            }
         }
         Ok(input_state)
     }
 }
 
+/// Metadata of a step result.
+/// The evaluation record of the context (query, status, timing, dependencies) is combined
+/// with the log collected so far and with the description of the data (key, type, format) from the state.
+fn result_metadata<ER: EnvRef<E>, E: Environment>(
+    context: &Context<ER, E>,
+    log: &[LogEntry],
+    state: &State<E::Value>,
+) -> Metadata {
+    let mut metadata = context.get_metadata();
+    metadata.log = log.to_vec();
+    match &*state.metadata {
+        Metadata::MetadataRecord(m) => {
+            metadata.key = m.key.clone();
+            metadata.filename = m.filename.clone();
+            metadata.media_type = m.media_type.clone();
+            metadata.data_format = m.data_format.clone();
+            metadata.type_identifier = m.type_identifier.clone();
+        }
+        Metadata::LegacyMetadata(_) => {
+            metadata.filename = state.metadata.filename();
+            metadata.media_type = state.metadata.get_media_type();
+            metadata.data_format = state.metadata.data_format();
+            metadata.type_identifier = state.metadata.type_identifier().unwrap_or_default();
+        }
+    }
+    if metadata.type_identifier.is_empty() {
+        metadata.type_identifier = state.data.identifier().to_string();
+    }
+    if metadata.media_type.is_empty() {
+        metadata.media_type = state.media_type();
+    }
+    Metadata::MetadataRecord(metadata)
+}
+
+/// Move the log entries of a step from the context to the evaluation log,
+/// so that they do not leak into the following steps.
+/// Entries without a position get the position of the action.
+fn collect_step_log<ER: EnvRef<E>, E: Environment>(
+    context: &Context<ER, E>,
+    step: &Step,
+    log: &mut Vec<LogEntry>,
+) {
+    for mut entry in context.take_log() {
+        if let Step::Action { position, .. } = step {
+            if entry.position.is_unknown() {
+                entry.with_position(position.clone());
+            }
+        }
+        log.push(entry);
+    }
+}
+
+/// Evaluate the links (link parameters and default queries) of action parameters
+/// and replace the parameter values with the results.
+/// Links are independent, so they may be evaluated in parallel.
+fn resolve_links<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>>(
+    parameters: &ResolvedParameters,
+    context: &Context<ER, E>,
+) -> Result<ResolvedParameters, Error> {
+    let mut parameters = parameters.clone();
+    if parameters.links.is_empty() {
+        return Ok(parameters);
+    }
+    let queries: Vec<Query> = parameters.links.iter().map(|(_, q)| q.clone()).collect();
+    let states = context.evaluate_dependencies(&queries)?;
+    for ((i, _), state) in parameters.links.iter().zip(states.iter()) {
+        if let Some(parameter) = parameters.parameters.get_mut(*i) {
+            parameter.value = state.data.try_into_json_value()?;
+        }
+    }
+    parameters.links.clear();
+    Ok(parameters)
+}
+
+/// Evaluate the action parameter links asynchronously, see [resolve_links]
+async fn resolve_links_async<ER, E>(
+    parameters: &ResolvedParameters,
+    context: &Context<ER, E>,
+) -> Result<ResolvedParameters, Error>
+where
+    ER: EnvRef<E> + MaybeSend + MaybeSync,
+    E: Environment<EnvironmentReference = ER> + MaybeSend + MaybeSync,
+    E::CommandExecutor: AsyncCommandExecutor<ER, E, E::Value>,
+{
+    let mut parameters = parameters.clone();
+    if parameters.links.is_empty() {
+        return Ok(parameters);
+    }
+    let queries: Vec<Query> = parameters.links.iter().map(|(_, q)| q.clone()).collect();
+    let states = context.evaluate_dependencies_async(&queries).await?;
+    for ((i, _), state) in parameters.links.iter().zip(states.iter()) {
+        if let Some(parameter) = parameters.parameters.get_mut(*i) {
+            parameter.value = state.data.try_into_json_value()?;
+        }
+    }
+    parameters.links.clear();
+    Ok(parameters)
+}
+
+/// Get the data and metadata of a store resource.
+/// A recipe which has not been computed yet (see [crate::store::RecipeStore]) is evaluated
+/// and the result is serialized according to the key and stored.
+fn get_resource<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>>(
+    key: &Key,
+    context: &Context<ER, E>,
+) -> Result<(Vec<u8>, Metadata), Error> {
+    match read_resource(key, context)? {
+        Ok(resource) => Ok(resource),
+        Err(query) => {
+            let state = context.evaluate_query(&query)?;
+            store_recipe_result(key, state, context)
+        }
+    }
+}
+
+/// Get the data and metadata of a store resource, evaluating recipes asynchronously, see [get_resource]
+async fn get_resource_async<ER, E>(
+    key: &Key,
+    context: &Context<ER, E>,
+) -> Result<(Vec<u8>, Metadata), Error>
+where
+    ER: EnvRef<E> + MaybeSend + MaybeSync,
+    E: Environment<EnvironmentReference = ER> + MaybeSend + MaybeSync,
+    E::CommandExecutor: AsyncCommandExecutor<ER, E, E::Value>,
+{
+    match read_resource(key, context)? {
+        Ok(resource) => Ok(resource),
+        Err(query) => {
+            let state = context.evaluate_query_async(&query).await?;
+            store_recipe_result(key, state, context)
+        }
+    }
+}
+
+/// Read a store resource; returns the recipe query instead if the recipe has not been evaluated yet.
+/// The store must not be locked during the recipe evaluation, the recipe may use other resources.
+fn read_resource<ER: EnvRef<E>, E: Environment>(
+    key: &Key,
+    context: &Context<ER, E>,
+) -> Result<Result<(Vec<u8>, Metadata), Query>, Error> {
+    let result = context.get_store().lock().unwrap().get(key);
+    match result {
+        Err(StoreError::RecipeNotEvaluated(_, query)) => {
+            context.info(&format!("Evaluating recipe {} for {}", query.encode(), key.encode()));
+            Ok(Err(query))
+        }
+        result => Ok(Ok(result?)),
+    }
+}
+
+/// Serialize the evaluated recipe according to the key and store it
+fn store_recipe_result<ER: EnvRef<E>, E: Environment>(
+    key: &Key,
+    state: State<E::Value>,
+    context: &Context<ER, E>,
+) -> Result<(Vec<u8>, Metadata), Error> {
+    let store = context.get_store();
+    let mut metadata = (*state.metadata).clone();
+    metadata.with_key(key.clone());
+    if let Some(name) = key.filename() {
+        metadata.with_filename(name.name.clone());
+    }
+    let state = state.with_metadata(metadata);
+    let data = context.get_serializer_registry().serialize_state(&state)?;
+    let mut metadata = (*state.metadata).clone();
+    metadata.with_data_format(state.data_format());
+    if let Metadata::MetadataRecord(m) = &mut metadata {
+        m.with_status(Status::Ready);
+    }
+    store.lock().unwrap().set(key, &data, &metadata)?;
+    Ok((data, metadata))
+}
+
+/// State of a store resource, deserialized according to the key or metadata (see [deserialize_resource])
+fn resource_state<ER: EnvRef<E>, E: Environment>(
+    key: &Key,
+    (data, mut metadata): (Vec<u8>, Metadata),
+    context: &Context<ER, E>,
+) -> Result<State<E::Value>, Error> {
+    context.add_dependency(Dependency::Key(key.clone()));
+    let registry = context.get_serializer_registry();
+    let (value, format) = deserialize_resource(&registry, key, data, &metadata)?;
+    metadata.with_key(key.clone()).with_data_format(format);
+    Ok(State::new().with_data(value).with_metadata(metadata))
+}
+
+/// State of a store resource as raw bytes
+fn binary_resource_state<ER: EnvRef<E>, E: Environment>(
+    key: &Key,
+    (data, mut metadata): (Vec<u8>, Metadata),
+    context: &Context<ER, E>,
+) -> State<E::Value> {
+    context.add_dependency(Dependency::Key(key.clone()));
+    let value = <E::Value as ValueInterface>::from_bytes(data);
+    metadata.with_key(key.clone()).with_data_format("b".to_string());
+    State::new().with_data(value).with_metadata(metadata)
+}
+
+/// Listing of a store directory (see [crate::store::Store::listdir_entries]) as a value
+fn get_directory_listing<ER: EnvRef<E>, E: Environment>(
+    key: &Key,
+    context: &Context<ER, E>,
+) -> Result<State<E::Value>, Error> {
+    let entries = context.get_store().lock().unwrap().listdir_entries(key)?;
+    let json = serde_json::to_value(entries).map_err(|e| {
+        Error::general_error(format!("Can't list {}: {}", key.encode(), e))
+    })?;
+    let value = <E::Value as ValueInterface>::try_from_json_value(&json)?;
+    let mut metadata = MetadataRecord::new();
+    metadata
+        .with_key(key.clone())
+        .with_media_type("application/json".to_string())
+        .with_data_format("json".to_string());
+    Ok(State::new()
+        .with_data(value)
+        .with_metadata(Metadata::MetadataRecord(metadata)))
+}
+
+/// Deserialize the data of a store resource.
+/// The format is taken from the key extension, the data format or the media type in the metadata.
+/// If the format is unknown or not supported by the value type, raw bytes are returned.
+/// Returns the value and the format used.
+fn deserialize_resource<V: ValueInterface>(
+    registry: &SerializerRegistry<V>,
+    key: &Key,
+    data: Vec<u8>,
+    metadata: &Metadata,
+) -> Result<(V, String), Error> {
+    let format = key
+        .extension()
+        .or_else(|| metadata.data_format())
+        .or_else(|| registry.format_from_media_type(&metadata.get_media_type()));
+    if let Some(format) = format {
+        let type_identifier = metadata
+            .type_identifier()
+            .ok()
+            .filter(|t| !t.is_empty())
+            .unwrap_or("generic".to_string());
+        match registry.deserialize(&data, &type_identifier, &format) {
+            Ok(value) => return Ok((value, format)),
+            Err(e) if e.error_type == ErrorType::NotSupported => {}
+            Err(e) => {
+                return Err(Error::new(
+                    e.error_type,
+                    format!("Can't load {} as {}: {}", key.encode(), format, e.message),
+                ))
+            }
+        }
+    }
+    Ok((V::from_bytes(data), "b".to_string()))
+}
+
+/// Prefixes of the plan query which may be taken from or stored in the cache.
+/// Each prefix is paired with the number of plan steps needed to evaluate it.
+/// The result is sorted by the number of steps, the full query being the last.
+fn cacheable_prefixes<E: Environment>(environment: &E, plan: &Plan) -> Vec<(usize, Query)> {
+    if plan.query.is_empty() {
+        return Vec::new();
+    }
+    let cmr = environment.get_command_metadata_registry();
+    let mut prefixes = vec![(plan.steps.len(), plan.query.clone())];
+    for (query, _) in plan.query.all_predecessor_tuples() {
+        if query.is_empty() {
+            continue;
+        }
+        if let Ok(prefix_plan) = PlanBuilder::new(query.clone(), cmr).build() {
+            let n = prefix_plan.steps.len();
+            if n > 0 && n <= plan.steps.len() && !prefixes.iter().any(|(m, _)| *m == n) {
+                prefixes.push((n, query));
+            }
+        }
+    }
+    prefixes.sort_by_key(|(n, _)| *n);
+    prefixes
+}
+
+/// Find the longest cached prefix of the plan.
+/// Returns the number of steps covered by the cache and the cached state (if any).
+/// Cache hit or miss is logged in the context.
+fn start_from_cache<ER: EnvRef<E>, E: Environment>(
+    cache: &Arc<Mutex<Box<dyn Cache<E::Value>>>>,
+    prefixes: &[(usize, Query)],
+    plan_length: usize,
+    context: &Context<ER, E>,
+) -> (usize, Option<State<E::Value>>) {
+    if prefixes.is_empty() {
+        return (0, None);
+    }
+    let cache = cache.lock().unwrap();
+    for (n, query) in prefixes.iter().rev() {
+        if !cache.contains(query) {
+            continue;
+        }
+        if let Ok(state) = cache.get(query, &context.get_serializer_registry()) {
+            let message = format!("Cache hit: {}", query.encode());
+            context.info(&message);
+            if *n == plan_length {
+                // Nothing is evaluated, so the hit is recorded directly in the cached state
+                let mut metadata = (*state.metadata).clone();
+                if let Metadata::MetadataRecord(m) = &mut metadata {
+                    m.info(&message);
+                }
+                return (*n, Some(state.with_metadata(metadata)));
+            }
+            return (*n, Some(state));
+        }
+    }
+    context.info(&format!("Cache miss: {}", context.get_query().encode()));
+    (0, None)
+}
+
+/// Returns false if the step result (and all the results depending on it) must not be cached
+fn is_cacheable_step<E: Environment>(environment: &E, step: &Step) -> bool {
+    if let Step::Action {
+        realm,
+        ns,
+        action_name,
+        ..
+    } = step
+    {
+        environment
+            .get_command_metadata_registry()
+            .find_command(realm, ns, action_name)
+            .map(|cm| cm.cache && !cm.volatile)
+            .unwrap_or(false)
+    } else {
+        true
+    }
+}
+
+/// Store the result of an action step in the cache if it corresponds to a query prefix
+fn store_in_cache<ER: EnvRef<E>, E: Environment>(
+    cache: &Arc<Mutex<Box<dyn Cache<E::Value>>>>,
+    prefixes: &[(usize, Query)],
+    steps_done: usize,
+    step: &Step,
+    state: &State<E::Value>,
+    context: &Context<ER, E>,
+) {
+    if !matches!(step, Step::Action { .. }) {
+        return;
+    }
+    if let Some((_, query)) = prefixes.iter().find(|(n, _)| *n == steps_done) {
+        let mut metadata = (*state.metadata).clone();
+        metadata.with_query(query.clone());
+        if let Metadata::MetadataRecord(m) = &mut metadata {
+            m.finish_evaluation();
+        }
+        let state = state.clone().with_metadata(metadata);
+        if let Err(e) = cache
+            .lock()
+            .unwrap()
+            .set(state, &context.get_serializer_registry())
+        {
+            if e.error_type != ErrorType::CacheNotSupported {
+                context.warning(&format!("Failed to cache {}: {}", query.encode(), e));
+            }
+        }
+    }
+}
+
+/// Step to execute together with its input state
+type StepInput<V> = (Step, State<V>);
+
+/// Evaluation of a plan shared by [PlanInterpreter::run] and [AsyncPlanInterpreter::run].
+/// Takes care of everything around the steps: hooks, cache lookup and storage, logs and error wrapping.
+/// The interpreter only executes the steps returned by [PlanRun::next_step].
+struct PlanRun<ER: EnvRef<E>, E: Environment> {
+    environment: ER,
+    context: Context<ER, E>,
+    plan: Plan,
+    hooks: Vec<Arc<dyn InterpreterHook<E>>>,
+    prefixes: Vec<(usize, Query)>,
+    cache: Arc<Mutex<Box<dyn Cache<E::Value>>>>,
+    log: Vec<LogEntry>,
+    started: bool,
+    from_cache: bool,
+    cacheable: bool,
+    step_number: usize,
+    state: Option<State<E::Value>>,
+}
+
+impl<ER: EnvRef<E>, E: Environment<EnvironmentReference = ER>> PlanRun<ER, E> {
+    /// Prepare the context of the evaluation; the query must not be already evaluated by the callers
+    fn new(
+        environment: ER,
+        plan: Option<&Plan>,
+        evaluation_stack: &[Query],
+        cancellation: &Cancellation,
+    ) -> Result<Self, Error> {
+        let plan = plan
+            .cloned()
+            .ok_or(Error::general_error("No plan".to_string()))?;
+        let mut context = environment.new_context();
+        if !plan.query.is_empty() {
+            if evaluation_stack.contains(&plan.query) {
+                return Err(Error::cyclic_dependency(&plan.query, evaluation_stack));
+            }
+            let mut evaluation_stack = evaluation_stack.to_vec();
+            evaluation_stack.push(plan.query.clone());
+            context.set_evaluation_stack(evaluation_stack);
+        }
+        context.set_cancellation(cancellation.clone());
+        context.set_query(plan.query.clone());
+        context.set_status(Status::Submitted);
+        let hooks = environment.get().get_hooks();
+        let prefixes = cacheable_prefixes(environment.get(), &plan);
+        let cache = environment.get().get_cache();
+        Ok(PlanRun {
+            environment,
+            context,
+            plan,
+            hooks,
+            prefixes,
+            cache,
+            log: Vec::new(),
+            started: false,
+            from_cache: false,
+            cacheable: true,
+            step_number: 0,
+            state: None,
+        })
+    }
+    /// Call the plan hooks and take the longest available prefix from the cache
+    fn start(&mut self) -> Result<(), Error> {
+        self.started = true;
+        if let Err(e) = self.hooks.before_plan(&self.plan, &self.context) {
+            let e = if e.query.is_none() && !self.plan.query.is_empty() {
+                e.with_query(&self.plan.query)
+            } else {
+                e
+            };
+            self.context.set_error(&e);
+            self.context.finish_evaluation();
+            let state = State::new();
+            self.state = Some(state.with_metadata(result_metadata(&self.context, &self.context.take_log(), &state)));
+            return Err(e);
+        }
+        let (start, state) = start_from_cache(&self.cache, &self.prefixes, self.plan.steps.len(), &self.context);
+        self.state = state;
+        self.step_number = start;
+        if start == self.plan.steps.len() && self.state.is_some() {
+            self.from_cache = true;
+            return Ok(());
+        }
+        self.context.start_evaluation();
+        self.log = self.context.take_log();
+        Ok(())
+    }
+    /// Next step to execute with its input state, None when the evaluation is finished
+    fn next_step(&mut self) -> Result<Option<StepInput<E::Value>>, Error> {
+        if !self.started {
+            self.start()?;
+        }
+        if self.from_cache {
+            return Ok(None);
+        }
+        if self.step_number >= self.plan.steps.len() {
+            self.context.finish_evaluation();
+            self.log.extend(self.context.take_log());
+            let state = self.state.take().unwrap_or_default();
+            self.state = Some(state.with_metadata(result_metadata(&self.context, &self.log, &state)));
+            self.from_cache = true;
+            return Ok(None);
+        }
+        let step = self.plan.steps[self.step_number].clone();
+        if self.context.is_cancelled() {
+            return Err(self.fail(&step, Error::cancelled()));
+        }
+        let input_state = self.state.take().unwrap_or_default();
+        if let Err(e) = self.hooks.before_step(&step, &input_state, &self.context) {
+            return Err(self.fail(&step, e));
+        }
+        Ok(Some((step, input_state)))
+    }
+    /// Record the result of the step returned by [PlanRun::next_step]
+    fn complete_step(
+        &mut self,
+        step: &Step,
+        input_state: &State<E::Value>,
+        result: Result<State<E::Value>, Error>,
+    ) -> Result<(), Error> {
+        let result = result.and_then(|output_state| {
+            collect_step_log(&self.context, step, &mut self.log);
+            let output_state =
+                output_state.with_metadata(result_metadata(&self.context, &self.log, &output_state));
+            self.hooks.after_step(step, input_state, &output_state, &self.context)?;
+            Ok(output_state)
+        });
+        match result {
+            Ok(output_state) => {
+                self.step_number += 1;
+                self.cacheable = self.cacheable && is_cacheable_step(self.environment.get(), step);
+                if self.cacheable {
+                    store_in_cache(&self.cache, &self.prefixes, self.step_number, step, &output_state, &self.context);
+                }
+                self.state = Some(output_state);
+                Ok(())
+            }
+            Err(e) => Err(self.fail(step, e)),
+        }
+    }
+    /// The failure is recorded in the metadata of the resulting state
+    fn fail(&mut self, step: &Step, e: Error) -> Error {
+        let query = self.context.get_query();
+        let e = if e.query.is_none() && !query.is_empty() {
+            e.with_query(&query)
+        } else {
+            e
+        };
+        self.hooks.on_error(step, &e, &self.context);
+        self.context.set_error(&e);
+        self.context.finish_evaluation();
+        collect_step_log(&self.context, step, &mut self.log);
+        let state = State::new();
+        self.state = Some(state.with_metadata(result_metadata(&self.context, &self.log, &state)));
+        e
+    }
+    /// Resulting state (also after a failure)
+    fn into_state(self) -> Option<State<E::Value>> {
+        self.state
+    }
+}
+
+/// Asynchronous plan interpreter
+/// Action steps are executed via the [AsyncCommandExecutor], so async and sync commands can be mixed in a query.
+/// Links, nested plans and recipes are evaluated by the asynchronous interpreter as well, so they may use async commands.
+/// The interpreter does not depend on any particular async runtime.
+pub struct AsyncPlanInterpreter<ER: EnvRef<E>, E: Environment> {
+    plan: Option<Plan>,
+    environment: ER,
+    state: Option<State<E::Value>>,
+    evaluation_stack: Vec<Query>,
+    cancellation: Cancellation,
+}
+
+impl<ER, E> AsyncPlanInterpreter<ER, E>
+where
+    ER: EnvRef<E> + MaybeSend + MaybeSync,
+    E: Environment<EnvironmentReference = ER> + MaybeSend + MaybeSync,
+    E::CommandExecutor: AsyncCommandExecutor<ER, E, E::Value>,
+{
+    pub fn new(environment: ER) -> Self {
+        AsyncPlanInterpreter {
+            plan: None,
+            environment,
+            state: None,
+            evaluation_stack: Vec::new(),
+            cancellation: Cancellation::new(),
+        }
+    }
+    /// Set the queries being evaluated by the callers (used to detect cyclic dependencies)
+    pub fn with_evaluation_stack(&mut self, evaluation_stack: Vec<Query>) -> &mut Self {
+        self.evaluation_stack = evaluation_stack;
+        self
+    }
+    /// Evaluation stops before the next step when the flag is cancelled
+    pub fn with_cancellation(&mut self, cancellation: Cancellation) -> &mut Self {
+        self.cancellation = cancellation;
+        self
+    }
+    /// Resulting state of the last run
+    pub fn get_state(&self) -> Option<State<E::Value>> {
+        self.state.clone()
+    }
+    pub fn with_plan(&mut self, plan: Plan) -> &mut Self {
+        self.plan = Some(plan);
+        self
+    }
+    pub fn with_query(&mut self, query: &str) -> Result<&mut Self, Error> {
+        let query = parse_query(query)?;
+        let cmr = self.environment.get().get_command_metadata_registry();
+        let mut pb = PlanBuilder::new(query, cmr);
+        let plan = pb.build()?;
+        Ok(self.with_plan(plan))
+    }
+    /// Evaluate the query and return the resulting state
+    pub async fn evaluate<Q: AsRef<str>>(&mut self, query: Q) -> Result<State<E::Value>, Error> {
+        self.with_query(query.as_ref())?;
+        self.run().await?;
+        self.state
+            .clone()
+            .ok_or(Error::general_error("No state after evaluation".to_string()))
+    }
+    /// Run the plan, using the cache the same way as [PlanInterpreter::run]
+    pub async fn run(&mut self) -> Result<(), Error> {
+        let mut run = PlanRun::new(
+            self.environment.get_ref(),
+            self.plan.as_ref(),
+            &self.evaluation_stack,
+            &self.cancellation,
+        )?;
+        let result = self.run_steps(&mut run).await;
+        self.state = run.into_state();
+        result
+    }
+    async fn run_steps(&self, run: &mut PlanRun<ER, E>) -> Result<(), Error> {
+        while let Some((step, input_state)) = run.next_step()? {
+            let result = self
+                .do_step(&step, input_state.clone(), run.context.clone_context())
+                .await;
+            run.complete_step(&step, &input_state, result)?;
+        }
+        Ok(())
+    }
+    /// Execute a step; links, nested plans and recipes are evaluated asynchronously as well.
+    /// The future is boxed, since the step may recursively evaluate other queries.
+    pub fn do_step<'a>(
+        &'a self,
+        step: &'a Step,
+        input_state: State<E::Value>,
+        context: Context<ER, E>,
+    ) -> BoxFuture<'a, Result<State<E::Value>, Error>> {
+        Box::pin(async move {
+            match step {
+                Step::Action {
+                    realm,
+                    ns,
+                    action_name,
+                    position,
+                    parameters,
+                } => {
+                    let mut arguments =
+                        CommandArguments::new(resolve_links_async(parameters, &context).await?);
+                    arguments.action_position = position.clone();
+
+                    let ce = self.environment.get().get_command_executor();
+                    let result = ce
+                        .async_execute(
+                            realm,
+                            ns,
+                            action_name,
+                            input_state,
+                            &mut arguments,
+                            context.clone_context(),
+                        )
+                        .await?;
+                    Ok(State::new().with_data(result))
+                }
+                Step::GetResource(key) | Step::GetNamedResource(key)
+                    if !context.get_store().lock().unwrap().is_dir(key) =>
+                {
+                    let resource = get_resource_async(key, &context).await?;
+                    resource_state(key, resource, &context)
+                }
+                Step::GetResourceBinary(key) => {
+                    let resource = get_resource_async(key, &context).await?;
+                    Ok(binary_resource_state(key, resource, &context))
+                }
+                Step::Evaluate(query) => context.evaluate_query_async(query).await,
+                Step::Plan(plan) => {
+                    let mut state = input_state;
+                    for step in plan.steps.iter() {
+                        state = self.do_step(step, state, context.clone_context()).await?;
+                    }
+                    Ok(state)
+                }
+                // The remaining steps do not evaluate queries
+                _ => PlanInterpreter::new(self.environment.get_ref()).do_step(step, input_state, context),
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
@@ -156,6 +1160,7 @@ mod tests {
     use crate::command_metadata::CommandMetadataRegistry;
     use crate::commands::*;
     use crate::context;
+    use crate::context::ArcEnvRef;
     use crate::context::SimpleEnvironment;
     use crate::context::StatEnvRef;
     use crate::metadata::Metadata;
@@ -474,4 +1479,1042 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_fallible_command_interpreter() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_command("hello", Command0::from(|| "Hello".to_string()))?;
+            cr.register_command(
+                "fail",
+                Command1::from(|_state: &State<Value>| -> Result<String, Error> {
+                    Err(Error::general_error("Failed".to_string()))
+                }),
+            )?;
+        }
+
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        pi.with_query("hello/fail").unwrap();
+        let e = pi.run().unwrap_err();
+        assert_eq!(e.message, "Failed");
+        assert_eq!(e.query, Some("hello/fail".to_string()));
+        assert!(!e.position.is_unknown());
+        if let Metadata::MetadataRecord(m) = &*pi.state.as_ref().unwrap().metadata {
+            assert_eq!(m.status, crate::metadata::Status::Error);
+            assert!(m.is_error);
+        } else {
+            panic!("MetadataRecord expected");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_predecessors() -> Result<(), Error> {
+        use crate::cache::MemoryCache;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_cache(Box::new(MemoryCache::new()));
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_command("hello", Command0::from(|| "Hello".to_string()))?;
+            let c = counter.clone();
+            cr.register_command(
+                "step",
+                Command2::from(move |state: &State<Value>, x: String| -> Result<String, Error> {
+                    c.fetch_add(1, Ordering::SeqCst);
+                    Ok(format!("{}{}", state.data.try_into_string()?, x))
+                }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("x"));
+            cr.register_command("now", Command1::from(|state: &State<Value>| state.data.try_into_string()))?
+                .volatile = true;
+        }
+        let pipeline = (1..9).map(|i| format!("step-{}", i)).collect::<Vec<_>>().join("/");
+
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate(&format!("hello/{}/step-9", pipeline))?;
+        assert_eq!(state.data.try_into_string()?, "Hello123456789");
+        assert_eq!(counter.load(Ordering::SeqCst), 9);
+
+        let state = pi.evaluate(&format!("hello/{}/step-0", pipeline))?;
+        assert_eq!(state.data.try_into_string()?, "Hello123456780");
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert!(m.log.iter().any(|entry| entry.message.starts_with("Cache hit")));
+        } else {
+            panic!("MetadataRecord expected");
+        }
+
+        let state = pi.evaluate(&format!("hello/{}/step-0", pipeline))?;
+        assert_eq!(state.data.try_into_string()?, "Hello123456780");
+        assert_eq!(counter.load(Ordering::SeqCst), 10);
+
+        // Results depending on a volatile command are not cached
+        pi.evaluate("hello/now/step-a")?;
+        pi.evaluate("hello/now/step-a")?;
+        assert_eq!(counter.load(Ordering::SeqCst), 12);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_interpreter() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_command("hello", Command0::from(|| "Hello".to_string()))?;
+            cr.register_async_command(
+                "greet",
+                AsyncCommand2::from(|state: State<Value>, who: String| async move {
+                    let greeting = state.data.try_into_string()?;
+                    Ok::<_, Error>(format!("{} {}!", greeting, who))
+                }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("who"));
+            cr.register_async_command(
+                "hi",
+                AsyncStatelessCommand1::from(|who: String| async move { format!("Hi {}!", who) }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("who"));
+        }
+        let envref = env.to_ref();
+
+        let mut pi = AsyncPlanInterpreter::new(envref.get_ref());
+        let state = pi.evaluate("hello/greet-world").await?;
+        assert_eq!(state.data.try_into_string()?, "Hello world!");
+
+        let state = pi.evaluate("hi-all").await?;
+        assert_eq!(state.data.try_into_string()?, "Hi all!");
+
+        // Queries evaluated by the callers are detected as cyclic dependencies
+        let mut pi = AsyncPlanInterpreter::new(envref.get_ref());
+        pi.with_evaluation_stack(vec![parse_query("hello/greet-world")?]);
+        let e = pi.evaluate("hello/greet-world").await.unwrap_err();
+        assert_eq!(e.error_type, ErrorType::CyclicDependency);
+
+        // Async commands are not available to the synchronous interpreter
+        let mut pi = PlanInterpreter::new(envref);
+        let e = pi.evaluate("hello/greet-world").unwrap_err();
+        assert_eq!(e.error_type, ErrorType::NotSupported);
+        assert!(e.message.contains("'greet'"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_subquery() -> Result<(), Error> {
+        type Env = SimpleEnvironment<Value>;
+        let mut env: Env = SimpleEnvironment::new();
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_async_command(
+                "hello",
+                AsyncCommand0::from(|| async { "Hello".to_string() }),
+            )?;
+            cr.register_async_command(
+                "report",
+                AsyncStatelessCommand1::from(|context: Context<ArcEnvRef<Env>, Env>| async move {
+                    let state = context.evaluate_async("hello").await?;
+                    Ok::<_, Error>(format!("{} report", state.data.try_into_string()?))
+                }),
+            )?;
+            cr.register_command(
+                "sync_report",
+                StatelessCommand1::from(|context: Context<ArcEnvRef<Env>, Env>| -> Result<String, Error> {
+                    context.evaluate("hello")?.data.try_into_string()
+                }),
+            )?;
+        }
+        let envref = env.to_ref();
+        let state = AsyncPlanInterpreter::new(envref.get_ref())
+            .evaluate("report")
+            .await?;
+        assert_eq!(state.data.try_into_string()?, "Hello report");
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert!(m.dependencies.contains(&Dependency::Query(parse_query("hello")?)));
+        }
+
+        // Synchronous sub-query can't use the async command
+        let e = AsyncPlanInterpreter::new(envref)
+            .evaluate("sync_report")
+            .await
+            .unwrap_err();
+        assert_eq!(e.error_type, ErrorType::NotSupported);
+        assert!(e.message.contains("'hello'"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_commands_in_links_plans_and_recipes() -> Result<(), Error> {
+        use crate::command_metadata::DefaultValue;
+        use crate::store::{MemoryStore, RecipeStore, Store};
+        let mut memory_store = MemoryStore::new(&Key::new());
+        memory_store.set(&parse_key("recipes.yaml")?, b"hello.txt: hello\n", &Metadata::new())?;
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(RecipeStore::new(Box::new(memory_store))));
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_async_command(
+                "hello",
+                AsyncCommand0::from(|| async { "Hello".to_string() }),
+            )?;
+            cr.register_command(
+                "greet",
+                Command3::from(|_state: &State<Value>, who: String, greeting: String| {
+                    format!("{}, {}", greeting, who)
+                }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("who"))
+            .with_argument(
+                ArgumentInfo::string_argument("greeting")
+                    .with_default_value(DefaultValue::from_query(parse_query("hello")?))
+                    .clone(),
+            );
+        }
+        let envref = env.to_ref();
+
+        // Link of the default query
+        let state = AsyncPlanInterpreter::new(envref.get_ref())
+            .evaluate("greet-world")
+            .await?;
+        assert_eq!(state.data.try_into_string()?, "Hello, world");
+
+        // Nested plan
+        let inner = PlanBuilder::new(parse_query("hello")?, envref.get().get_command_metadata_registry()).build()?;
+        let mut plan = Plan::new();
+        plan.steps.push(Step::Plan(inner));
+        let mut pi = AsyncPlanInterpreter::new(envref.get_ref());
+        pi.with_plan(plan).run().await?;
+        assert_eq!(pi.get_state().unwrap().data.try_into_string()?, "Hello");
+
+        // Recipe
+        let state = AsyncPlanInterpreter::new(envref.get_ref())
+            .evaluate("-R/hello.txt")
+            .await?;
+        assert_eq!(state.data.try_into_string()?, "Hello");
+        Ok(())
+    }
+
+    #[cfg(feature = "thread-safe")]
+    #[tokio::test]
+    async fn test_async_interpreter_is_send() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.get_mut_command_executor()
+            .register_async_command(
+                "hi",
+                AsyncStatelessCommand1::from(|who: String| async move { format!("Hi {}!", who) }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("who"));
+        let envref = env.to_ref();
+        // The evaluation future is Send, so it can be spawned on a multi-threaded runtime
+        let state = tokio::spawn(async move {
+            AsyncPlanInterpreter::new(envref).evaluate("hi-all").await
+        })
+        .await
+        .unwrap()?;
+        assert_eq!(state.data.try_into_string()?, "Hi all!");
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_steps() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(crate::store::MemoryStore::new(&Key::new())));
+        {
+            let store = env.get_store();
+            let mut store = store.lock().unwrap();
+            let mut metadata = MetadataRecord::new();
+            metadata.with_message("stored".to_string());
+            store.set(
+                &parse_key("hello.txt")?,
+                "Hello TEXT".as_bytes(),
+                &Metadata::MetadataRecord(metadata),
+            )?;
+            store.set(
+                &parse_key("data/hello.txt")?,
+                "Hello DATA".as_bytes(),
+                &Metadata::new(),
+            )?;
+        }
+        env.get_mut_command_executor()
+            .register_command("hello", Command0::from(|| "Hello".to_string()))?;
+        let envref = env.to_ref();
+
+        let mut pi = PlanInterpreter::new(envref.get_ref());
+        let state = pi.evaluate("-R-meta/hello.txt")?;
+        assert_eq!(
+            state.data.try_into_json_value()?["message"],
+            serde_json::Value::String("stored".to_string())
+        );
+
+        let state = pi.evaluate("-Rdata/hello.txt")?;
+        assert_eq!(state.data.try_into_string()?, "Hello DATA");
+
+        let mut nested = crate::plan::Plan::new();
+        nested.steps.push(Step::Evaluate(parse_query("hello")?));
+        nested.steps.push(Step::Info("nested".to_string()));
+        let mut plan = crate::plan::Plan::new();
+        plan.steps.push(Step::Plan(nested));
+        pi.with_plan(plan).run()?;
+        assert_eq!(pi.state.as_ref().unwrap().data.try_into_string()?, "Hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_formats() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(crate::store::MemoryStore::new(&Key::new())));
+        {
+            let store = env.get_store();
+            let mut store = store.lock().unwrap();
+            store.set(&parse_key("data.json")?, br#"{"a":1}"#, &Metadata::new())?;
+            let mut metadata = MetadataRecord::new();
+            metadata.with_media_type("application/json".to_string());
+            store.set(
+                &parse_key("data")?,
+                b"[1,2]",
+                &Metadata::MetadataRecord(metadata),
+            )?;
+            store.set(&parse_key("image.png")?, b"PNG", &Metadata::new())?;
+            store.set(&parse_key("broken.json")?, b"{", &Metadata::new())?;
+        }
+        let mut pi = PlanInterpreter::new(env.to_ref());
+
+        let state = pi.evaluate("-R/data.json")?;
+        assert!(matches!(&*state.data, Value::Object(_)));
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert_eq!(m.key.as_ref().unwrap().encode(), "data.json");
+            assert_eq!(m.data_format, Some("json".to_string()));
+        } else {
+            panic!("MetadataRecord expected");
+        }
+
+        let state = pi.evaluate("-R/data")?;
+        assert!(matches!(&*state.data, Value::Array(_)));
+
+        let state = pi.evaluate("-R/image.png")?;
+        assert!(matches!(&*state.data, Value::Bytes(_)));
+        assert_eq!(state.metadata.data_format(), Some("b".to_string()));
+
+        let state = pi.evaluate("-R-raw/data.json")?;
+        assert!(matches!(&*state.data, Value::Bytes(_)));
+        assert_eq!(state.metadata.data_format(), Some("b".to_string()));
+
+        let e = pi.evaluate("-R/broken.json").unwrap_err();
+        assert_eq!(e.error_type, crate::error::ErrorType::SerializationError);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filename_serialization() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.get_mut_command_executor()
+            .register_command("hello", Command0::from(|| "Hello".to_string()))?;
+        let mut pi = PlanInterpreter::new(env.to_ref());
+
+        let state = pi.evaluate("hello/result.json")?;
+        assert_eq!(state.as_bytes()?, b"\"Hello\"".to_vec());
+        assert_eq!(state.metadata.get_media_type(), "application/json");
+        assert_eq!(state.metadata.data_format(), Some("json".to_string()));
+
+        let state = pi.evaluate("hello/greeting.txt")?;
+        assert_eq!(state.as_bytes()?, b"Hello".to_vec());
+        assert_eq!(state.media_type(), "text/plain");
+
+        let e = pi.evaluate("hello/data.xyz").unwrap_err();
+        assert_eq!(e.error_type, crate::error::ErrorType::SerializationError);
+        assert!(e.message.contains("text") && e.message.contains("xyz"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_metadata() -> Result<(), Error> {
+        type Env = SimpleEnvironment<Value>;
+        type Ctx = Context<crate::context::ArcEnvRef<Env>, Env>;
+        let mut env: Env = SimpleEnvironment::new();
+        env.with_store(Box::new(crate::store::MemoryStore::new(&Key::new())));
+        env.get_store()
+            .lock()
+            .unwrap()
+            .set(&parse_key("a.txt")?, b"A", &Metadata::new())?;
+        env.get_mut_command_executor()
+            .register_command(
+                "add",
+                Command3::from(|state: &State<Value>, context: Ctx, x: String| -> Result<String, Error> {
+                    context.info(&format!("adding {}", x));
+                    Ok(format!("{}{}", state.data.try_into_string()?, x))
+                }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("x"));
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("-R/a.txt/-/add-x/add-y")?;
+        assert_eq!(state.data.try_into_string()?, "Axy");
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert_eq!(m.query.encode(), "-R/a.txt/-/add-x/add-y");
+            assert_eq!(m.status, crate::metadata::Status::Ready);
+            assert!(!m.started.is_empty());
+            assert!(!m.finished.is_empty());
+            assert!(m.duration_ms.is_some());
+            assert_eq!(m.key, None);
+            assert_eq!(m.type_identifier, "text");
+            assert_eq!(
+                m.dependencies,
+                vec![crate::metadata::Dependency::Key(parse_key("a.txt")?)]
+            );
+            let log: Vec<_> = m
+                .log
+                .iter()
+                .filter(|e| !e.message.starts_with("Cache"))
+                .collect();
+            let messages: Vec<_> = log.iter().map(|e| e.message.as_str()).collect();
+            assert_eq!(messages, vec!["adding x", "adding y"]);
+            assert!(log[0].position.offset < log[1].position.offset);
+        } else {
+            panic!("MetadataRecord expected");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_evaluate() -> Result<(), Error> {
+        type Env = SimpleEnvironment<Value>;
+        type Ctx = Context<crate::context::ArcEnvRef<Env>, Env>;
+        let mut env: Env = SimpleEnvironment::new();
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_command("hello", Command0::from(|| "Hello".to_string()))?;
+            cr.register_command(
+                "report",
+                Command2::from(|_state: &State<Value>, context: Ctx| -> Result<String, Error> {
+                    let a = context.evaluate("hello")?;
+                    let b = context.evaluate("hello/world")?;
+                    Ok(format!("{} {}", a.data.try_into_string()?, b.data.try_into_string()?))
+                }),
+            )?;
+            cr.register_command(
+                "world",
+                Command1::from(|state: &State<Value>| -> Result<String, Error> {
+                    Ok(format!("{} world", state.data.try_into_string()?))
+                }),
+            )?;
+            cr.register_command(
+                "cycle",
+                Command2::from(|_state: &State<Value>, context: Ctx| -> Result<String, Error> {
+                    context.evaluate("cycle")?.data.try_into_string()
+                }),
+            )?;
+        }
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("report")?;
+        assert_eq!(state.data.try_into_string()?, "Hello Hello world");
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert_eq!(
+                m.dependencies,
+                vec![
+                    crate::metadata::Dependency::Query(parse_query("hello")?),
+                    crate::metadata::Dependency::Query(parse_query("hello/world")?),
+                ]
+            );
+        } else {
+            panic!("MetadataRecord expected");
+        }
+
+        let e = pi.evaluate("cycle").unwrap_err();
+        assert_eq!(e.error_type, crate::error::ErrorType::CyclicDependency);
+        Ok(())
+    }
+
+    #[cfg(feature = "thread-safe")]
+    #[test]
+    fn test_parallel_links() -> Result<(), Error> {
+        use crate::command_metadata::DefaultValue;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_max_parallel_evaluations(3);
+        {
+            let cr = env.get_mut_command_executor();
+            let (r, m) = (running.clone(), max_running.clone());
+            cr.register_command(
+                "slow",
+                Command2::from(move |_state: &State<Value>, x: String| -> Result<String, Error> {
+                    let n = r.fetch_add(1, Ordering::SeqCst) + 1;
+                    m.fetch_max(n, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    r.fetch_sub(1, Ordering::SeqCst);
+                    if x == "fail" {
+                        Err(Error::general_error("Failed".to_string()))
+                    } else {
+                        Ok(x.to_uppercase())
+                    }
+                }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("x"));
+            let join = cr.register_command(
+                "join",
+                Command4::from(|_state: &State<Value>, a: String, b: String, c: String| {
+                    format!("{}{}{}", a, b, c)
+                }),
+            )?;
+            for (name, query) in [("a", "slow-a"), ("b", "slow-b"), ("c", "slow-c")] {
+                join.with_argument(
+                    ArgumentInfo::string_argument(name)
+                        .with_default_value(DefaultValue::from_query(parse_query(query)?))
+                        .clone(),
+                );
+            }
+            cr.register_command(
+                "fail",
+                Command2::from(|_state: &State<Value>, a: String| a),
+            )?
+            .with_argument(
+                ArgumentInfo::string_argument("a")
+                    .with_default_value(DefaultValue::from_query(parse_query("slow-fail")?))
+                    .clone(),
+            );
+        }
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("join")?;
+        assert_eq!(state.data.try_into_string()?, "ABC");
+        assert!(max_running.load(Ordering::SeqCst) > 1);
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            let queries: Vec<_> = m.dependency_timings.iter().map(|t| t.query.encode()).collect();
+            assert_eq!(queries, vec!["slow-a", "slow-b", "slow-c"]);
+            assert!(m.dependency_timings.iter().all(|t| t.duration_ms.is_some()));
+        } else {
+            panic!("MetadataRecord expected");
+        }
+
+        let e = pi.evaluate("fail").unwrap_err();
+        assert_eq!(e.message, "Failed");
+        Ok(())
+    }
+
+    #[cfg(feature = "thread-safe")]
+    #[test]
+    fn test_nested_links_share_thread_pool() -> Result<(), Error> {
+        use crate::command_metadata::DefaultValue;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_max_parallel_evaluations(2);
+        {
+            let cr = env.get_mut_command_executor();
+            let (r, m) = (running.clone(), max_running.clone());
+            cr.register_command(
+                "slow",
+                Command2::from(move |_state: &State<Value>, x: String| -> String {
+                    let n = r.fetch_add(1, Ordering::SeqCst) + 1;
+                    m.fetch_max(n, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    r.fetch_sub(1, Ordering::SeqCst);
+                    x
+                }),
+            )?
+            .with_argument(ArgumentInfo::string_argument("x"));
+            let pair = cr.register_command(
+                "pair",
+                Command3::from(|_state: &State<Value>, a: String, b: String| format!("{}{}", a, b)),
+            )?;
+            for (name, query) in [("a", "slow-a"), ("b", "slow-b")] {
+                pair.with_argument(
+                    ArgumentInfo::string_argument(name)
+                        .with_default_value(DefaultValue::from_query(parse_query(query)?))
+                        .clone(),
+                );
+            }
+            let outer = cr.register_command(
+                "outer",
+                Command4::from(|_state: &State<Value>, a: String, b: String, c: String| {
+                    format!("{}-{}-{}", a, b, c)
+                }),
+            )?;
+            for (name, query) in [("a", "pair"), ("b", "pair/slow-x"), ("c", "pair/slow-y")] {
+                outer.with_argument(
+                    ArgumentInfo::string_argument(name)
+                        .with_default_value(DefaultValue::from_query(parse_query(query)?))
+                        .clone(),
+                );
+            }
+        }
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        let state = pi.evaluate("outer")?;
+        assert_eq!(state.data.try_into_string()?, "ab-x-y");
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancelled_evaluation() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.get_mut_command_executor()
+            .register_command("hello", Command1::from(|_state: &State<Value>| "Hello"))?;
+        let cancellation = Cancellation::new();
+        let child = cancellation.child();
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        pi.with_cancellation(child.clone());
+        assert_eq!(pi.evaluate("hello")?.data.try_into_string()?, "Hello");
+        cancellation.cancel();
+        assert!(child.is_cancelled());
+        let e = pi.evaluate("hello").unwrap_err();
+        assert_eq!(e.error_type, ErrorType::Cancelled);
+        Ok(())
+    }
+
+    #[test]
+    fn test_debugging() -> Result<(), Error> {
+        type Env = SimpleEnvironment<Value>;
+        type Ctx = Context<crate::context::ArcEnvRef<Env>, Env>;
+        let mut env: Env = SimpleEnvironment::new();
+        env.with_store(Box::new(crate::store::MemoryStore::new(&Key::new())));
+        env.get_store()
+            .lock()
+            .unwrap()
+            .set(&parse_key("a.txt")?, b"A", &Metadata::new())?;
+        let cr = env.get_mut_command_executor();
+        cr.register_command(
+            "add",
+            Command3::from(|state: &State<Value>, context: Ctx, x: String| -> Result<String, Error> {
+                context.info(&format!("adding {}", x));
+                Ok(format!("{}{}", state.data.try_into_string()?, x))
+            }),
+        )?
+        .with_argument(ArgumentInfo::string_argument("x"));
+        cr.register_command(
+            "upper",
+            Command1::from(|state: &State<Value>| -> Result<String, Error> {
+                Ok(state.data.try_into_string()?.to_uppercase())
+            }),
+        )?;
+        let mut pi = PlanInterpreter::new(env.to_ref());
+        pi.with_query("-R/a.txt/-/add-x/upper/add-y")?;
+        assert_eq!(pi.len(), 4);
+        pi.with_breakpoint(Breakpoint::Action("upper".to_string()));
+
+        assert_eq!(pi.continue_to_breakpoint()?, Some(2));
+        assert_eq!(pi.get_state().unwrap().data.try_into_string()?, "Ax");
+        assert_eq!(pi.get_intermediate_state(1).unwrap().data.try_into_string()?, "A");
+        assert!(pi.get_intermediate_state(0).unwrap().data.is_none());
+
+        let state = pi.step()?;
+        assert_eq!(state.data.try_into_string()?, "AX");
+        assert_eq!(pi.continue_to_breakpoint()?, None);
+        assert!(pi.is_finished());
+        let state = pi.get_state().unwrap();
+        assert_eq!(state.data.try_into_string()?, "AXy");
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert_eq!(m.status, crate::metadata::Status::Ready);
+            assert_eq!(m.query.encode(), "-R/a.txt/-/add-x/upper/add-y");
+            let messages: Vec<_> = m.log.iter().map(|e| e.message.as_str()).collect();
+            assert_eq!(messages, vec!["adding x", "adding y"]);
+            assert_eq!(
+                m.dependencies,
+                vec![crate::metadata::Dependency::Key(parse_key("a.txt")?)]
+            );
+        } else {
+            panic!("MetadataRecord expected");
+        }
+
+        // Replay from the first action with a modified parameter
+        pi.set_parameter(1, 0, serde_json::Value::String("z".to_string()))?;
+        assert_eq!(pi.step_number(), 1);
+        assert_eq!(pi.get_state().unwrap().data.try_into_string()?, "A");
+        assert!(pi.get_intermediate_state(2).is_none());
+        pi.clear_breakpoints().with_breakpoint(Breakpoint::Step(3));
+        assert_eq!(pi.continue_to_breakpoint()?, Some(3));
+        assert_eq!(pi.get_state().unwrap().data.try_into_string()?, "AZ");
+        assert_eq!(pi.continue_to_breakpoint()?, None);
+        assert_eq!(pi.get_state().unwrap().data.try_into_string()?, "AZy");
+
+        assert!(pi.step().is_err());
+        assert!(pi.set_parameter(2, 0, serde_json::Value::Null).is_err());
+        assert!(pi.rewind(5).is_err());
+        pi.rewind(0)?;
+        assert_eq!(pi.step()?.data.try_into_string()?, "A");
+        Ok(())
+    }
+
+    #[test]
+    fn test_hooks() -> Result<(), Error> {
+        type Env = SimpleEnvironment<Value>;
+        type Ctx = Context<crate::context::ArcEnvRef<Env>, Env>;
+
+        #[derive(Default)]
+        struct Audit {
+            events: Mutex<Vec<String>>,
+        }
+        impl InterpreterHook<Env> for Audit {
+            fn before_plan(&self, plan: &Plan, _context: &Ctx) -> Result<(), Error> {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("plan {}", plan.query.encode()));
+                Ok(())
+            }
+            fn after_step(
+                &self,
+                step: &Step,
+                input_state: &State<Value>,
+                output_state: &State<Value>,
+                _context: &Ctx,
+            ) -> Result<(), Error> {
+                if let Step::Action { action_name, .. } = step {
+                    self.events.lock().unwrap().push(format!(
+                        "{}: {} -> {}",
+                        action_name,
+                        input_state.data.try_into_string().unwrap_or_default(),
+                        output_state.data.try_into_string()?
+                    ));
+                }
+                Ok(())
+            }
+            fn on_error(&self, _step: &Step, error: &Error, _context: &Ctx) {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("error {}", error.message));
+            }
+        }
+
+        struct Policy;
+        impl InterpreterHook<Env> for Policy {
+            fn before_step(&self, step: &Step, _input_state: &State<Value>, context: &Ctx) -> Result<(), Error> {
+                match step {
+                    Step::Action { action_name, .. } if action_name == "forbidden" => {
+                        Err(Error::general_error("forbidden is not allowed".to_string()))
+                    }
+                    _ => {
+                        context.debug("allowed");
+                        Ok(())
+                    }
+                }
+            }
+        }
+
+        let audit = Arc::new(Audit::default());
+        let mut env: Env = SimpleEnvironment::new();
+        env.with_hook(audit.clone()).with_hook(Arc::new(Policy));
+        let cr = env.get_mut_command_executor();
+        cr.register_command("hello", Command1::from(|_state: &State<Value>| "Hello".to_string()))?;
+        cr.register_command(
+            "upper",
+            Command1::from(|state: &State<Value>| -> Result<String, Error> {
+                Ok(state.data.try_into_string()?.to_uppercase())
+            }),
+        )?;
+        let executed = Arc::new(Mutex::new(false));
+        let e = executed.clone();
+        cr.register_command(
+            "forbidden",
+            Command1::from(move |_state: &State<Value>| {
+                *e.lock().unwrap() = true;
+                "Forbidden".to_string()
+            }),
+        )?;
+        let env = env.to_ref();
+
+        let state = PlanInterpreter::new(env.clone()).evaluate("hello/upper")?;
+        assert_eq!(state.data.try_into_string()?, "HELLO");
+        if let Metadata::MetadataRecord(m) = &*state.metadata {
+            assert!(m.log.iter().any(|entry| entry.message == "allowed"));
+        } else {
+            panic!("MetadataRecord expected");
+        }
+
+        let mut pi = PlanInterpreter::new(env.clone());
+        let e = pi.evaluate("hello/forbidden").unwrap_err();
+        assert_eq!(e.message, "forbidden is not allowed");
+        assert!(!*executed.lock().unwrap());
+        if let Metadata::MetadataRecord(m) = &*pi.get_state().unwrap().metadata {
+            assert_eq!(m.status, crate::metadata::Status::Error);
+        }
+
+        assert_eq!(
+            *audit.events.lock().unwrap(),
+            vec![
+                "plan hello/upper",
+                "hello:  -> Hello",
+                "upper: Hello -> HELLO",
+                "plan hello/forbidden",
+                "hello:  -> Hello",
+                "error forbidden is not allowed",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_recipes() -> Result<(), Error> {
+        use crate::store::{MemoryStore, RecipeStore, Store};
+        let counter = Arc::new(Mutex::new(0));
+        let mut memory_store = MemoryStore::new(&Key::new());
+        memory_store.set(
+            &parse_key("recipes.yaml")?,
+            b"hello.txt: hello/add-world\nfail.txt: failing\n",
+            &Metadata::new(),
+        )?;
+        let mut store = RecipeStore::new(Box::new(memory_store));
+        store.with_recipe(&parse_key("loop.txt")?, parse_query("-R/loop.txt")?);
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(store));
+        let cr = env.get_mut_command_executor();
+        let c = counter.clone();
+        cr.register_command(
+            "hello",
+            Command1::from(move |_state: &State<Value>| {
+                *c.lock().unwrap() += 1;
+                "Hello".to_string()
+            }),
+        )?;
+        cr.register_command(
+            "add",
+            Command2::from(|state: &State<Value>, x: String| -> Result<String, Error> {
+                Ok(format!("{}, {}", state.data.try_into_string()?, x))
+            }),
+        )?
+        .with_argument(ArgumentInfo::string_argument("x"));
+        cr.register_command(
+            "failing",
+            Command1::from(|_state: &State<Value>| -> Result<String, Error> {
+                Err(Error::general_error("Recipe failed".to_string()))
+            }),
+        )?;
+        let env = env.to_ref();
+
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R/hello.txt/-/add-again")?;
+        assert_eq!(state.data.try_into_string()?, "Hello, world, again");
+        let (data, metadata) = env.get_store().lock().unwrap().get(&parse_key("hello.txt")?)?;
+        assert_eq!(data, b"Hello, world");
+        assert_eq!(metadata.status(), crate::metadata::Status::Ready);
+        assert_eq!(metadata.query()?.encode(), "hello/add-world");
+
+        // The stored result is used
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R/hello.txt")?;
+        assert_eq!(state.data.try_into_string()?, "Hello, world");
+        assert_eq!(*counter.lock().unwrap(), 1);
+
+        let e = PlanInterpreter::new(env.clone()).evaluate("-R/fail.txt").unwrap_err();
+        assert_eq!(e.message, "Recipe failed");
+        let e = PlanInterpreter::new(env.clone()).evaluate("-R/loop.txt").unwrap_err();
+        assert_eq!(e.error_type, ErrorType::CyclicDependency);
+        Ok(())
+    }
+
+    #[test]
+    fn test_put() -> Result<(), Error> {
+        use crate::metadata::Status;
+        use crate::store::MemoryStore;
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(MemoryStore::new(&Key::new())))
+            .with_writable_prefix(parse_key("results")?);
+        let cr = env.get_mut_command_executor();
+        register_store_commands(cr)?;
+        cr.register_command("hello", Command1::from(|_state: &State<Value>| "Hello".to_string()))?;
+        let env = env.to_ref();
+
+        let query = "hello/put-results~Iweekly.txt";
+        let state = PlanInterpreter::new(env.clone()).evaluate(query)?;
+        assert_eq!(state.data.try_into_string()?, "Hello");
+        let (data, metadata) = env.get_store().lock().unwrap().get(&parse_key("results/weekly.txt")?)?;
+        assert_eq!(data, b"Hello");
+        assert_eq!(metadata.status(), Status::SideEffect);
+        assert_eq!(metadata.query()?.encode(), query);
+        assert_eq!(metadata.data_format(), Some("txt".to_string()));
+
+        let e = PlanInterpreter::new(env.clone())
+            .evaluate("hello/put-other~Iweekly.txt")
+            .unwrap_err();
+        assert_eq!(e.error_type, ErrorType::PermissionDenied);
+        assert!(!env.get_store().lock().unwrap().contains(&parse_key("other/weekly.txt")?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_put_outside_of_store() -> Result<(), Error> {
+        use crate::store::FileStore;
+        let dir = std::env::temp_dir().join(format!("liquers-put-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("results")).unwrap();
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(FileStore::new(root.to_str().unwrap(), &Key::new())))
+            .with_writable_prefix(parse_key("results")?);
+        let cr = env.get_mut_command_executor();
+        register_store_commands(cr)?;
+        cr.register_command("hello", Command1::from(|_state: &State<Value>| "Hello".to_string()))?;
+        let env = env.to_ref();
+
+        for query in [
+            "hello/put-results~I..~I..~Iescaped.txt",
+            "hello/put-results~I.~Idot.txt",
+        ] {
+            let e = PlanInterpreter::new(env.clone()).evaluate(query).unwrap_err();
+            assert_eq!(e.error_type, ErrorType::PermissionDenied);
+        }
+        let written: Vec<_> = walk(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(written.is_empty(), "Unexpected files: {:?}", written);
+        return Ok(());
+
+        fn walk(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+            std::fs::read_dir(dir)
+                .unwrap()
+                .flat_map(|entry| {
+                    let path = entry.unwrap().path();
+                    if path.is_dir() { walk(&path) } else { vec![path] }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_list_and_json_parameters() -> Result<(), Error> {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        let cr = env.get_mut_command_executor();
+        cr.register_command(
+            "sum",
+            Command2::from(|_state: &State<Value>, x: Vec<i32>| x.iter().sum::<i32>()),
+        )?
+        .with_argument(ArgumentInfo::string_argument("x"));
+        cr.register_command(
+            "keys",
+            Command2::from(|_state: &State<Value>, x: serde_json::Value| {
+                x.as_object()
+                    .map(|o| o.keys().cloned().collect::<Vec<_>>().join(" "))
+                    .unwrap_or_default()
+            }),
+        )?
+        .with_argument(ArgumentInfo::string_argument("x"));
+        let env = env.to_ref();
+        let sum = |list: &str| -> Result<i32, Error> {
+            PlanInterpreter::new(env.clone())
+                .evaluate(&format!("sum-{}", crate::query::encode_token(list)))?
+                .data
+                .try_into_i32()
+        };
+        assert_eq!(sum("1,2,3")?, 6);
+        assert_eq!(sum("1,-2,-3")?, -4, "negative numbers in a list");
+        let state = PlanInterpreter::new(env.clone()).evaluate(&format!(
+            "keys-{}",
+            crate::query::encode_token(r#"{"a":1,"b":[2]}"#)
+        ))?;
+        assert_eq!(state.data.try_into_string()?, "a b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_listing() -> Result<(), Error> {
+        use crate::store::MemoryStore;
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(MemoryStore::new(&Key::new())));
+        env.get_store()
+            .lock()
+            .unwrap()
+            .set(&parse_key("dir/a.txt")?, b"A", &Metadata::new())?;
+        env.get_store()
+            .lock()
+            .unwrap()
+            .set(&parse_key("dir/sub/b.txt")?, b"B", &Metadata::new())?;
+        env.get_mut_command_executor()
+            .register_command(
+                "count",
+                Command1::from(|state: &State<Value>| -> Result<i64, Error> {
+                    match state.data.try_into_json_value()? {
+                        serde_json::Value::Array(entries) => Ok(entries.len() as i64),
+                        _ => Err(Error::general_error("Listing expected".to_string())),
+                    }
+                }),
+            )?;
+        let env = env.to_ref();
+
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R/dir")?;
+        let json = state.data.try_into_json_value()?;
+        assert_eq!(json[0]["name"], "a.txt");
+        assert_eq!(json[0]["is_dir"], false);
+        assert_eq!(json[0]["size"], 1);
+        assert_eq!(json[0]["media_type"], "text/plain");
+        assert_eq!(json[1]["name"], "sub");
+        assert_eq!(json[1]["is_dir"], true);
+        assert_eq!(state.metadata.get_media_type(), "application/json");
+
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R/dir/-/count")?;
+        assert_eq!(state.data.try_into_json_value()?, serde_json::json!(2));
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R/dir/a.txt")?;
+        assert_eq!(state.data.try_into_string()?, "A");
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R-dir/dir/sub")?;
+        assert_eq!(state.data.try_into_json_value()?[0]["key"], "dir/sub/b.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_serializer() -> Result<(), Error> {
+        use crate::serializer::FnSerializer;
+        use crate::store::MemoryStore;
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_store(Box::new(MemoryStore::new(&Key::new())));
+        env.get_store()
+            .lock()
+            .unwrap()
+            .set(&parse_key("a.rev")?, b"cba", &Metadata::new())?;
+        env.get_mut_serializer_registry().register(
+            "*",
+            "rev",
+            Arc::new(FnSerializer::new(
+                |value: &Value, _format: &str| -> Result<Vec<u8>, Error> {
+                    Ok(value.try_into_string()?.chars().rev().collect::<String>().into_bytes())
+                },
+                |data: &[u8], _type_identifier: &str, _format: &str| -> Result<Value, Error> {
+                    Ok(Value::from(String::from_utf8_lossy(data).chars().rev().collect::<String>()))
+                },
+            )),
+        );
+        env.get_mut_command_executor()
+            .register_command("hello", Command1::from(|_state: &State<Value>| "Hello".to_string()))?;
+        let env = env.to_ref();
+
+        let state = PlanInterpreter::new(env.clone()).evaluate("-R/a.rev")?;
+        assert_eq!(state.data.try_into_string()?, "abc");
+        let state = PlanInterpreter::new(env.clone()).evaluate("hello/hello.rev")?;
+        assert_eq!(env.get().get_serializer_registry().serialize_state(&state)?, b"olleH");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_uses_environment_serializers() -> Result<(), Error> {
+        use crate::cache::{MemoryBinCache, SerializingCache};
+        use crate::serializer::FnSerializer;
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        env.with_cache(Box::new(SerializingCache::new(MemoryBinCache::new())));
+        // Registered after the cache has been installed
+        env.get_mut_serializer_registry().register(
+            "*",
+            "txt",
+            Arc::new(FnSerializer::new(
+                |value: &Value, _format: &str| -> Result<Vec<u8>, Error> {
+                    Ok(value.try_into_string()?.chars().rev().collect::<String>().into_bytes())
+                },
+                |data: &[u8], _type_identifier: &str, _format: &str| -> Result<Value, Error> {
+                    Ok(Value::from(String::from_utf8_lossy(data).chars().rev().collect::<String>()))
+                },
+            )),
+        );
+        env.get_mut_command_executor()
+            .register_command("hello", Command1::from(|_state: &State<Value>| "Hello".to_string()))?;
+        let env = env.to_ref();
+
+        PlanInterpreter::new(env.clone()).evaluate("hello")?;
+        let query = parse_query("hello")?;
+        assert_eq!(
+            env.get().get_cache().lock().unwrap().get_binary(&query),
+            Some(b"olleH".to_vec())
+        );
+        let state = PlanInterpreter::new(env.clone()).evaluate("hello")?;
+        assert_eq!(state.data.try_into_string()?, "Hello");
+        Ok(())
+    }
 }
@@ -1,6 +1,8 @@
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+// Allows code generated by liquers-macro (referring to ::liquers_core) to be used inside this crate
+extern crate self as liquers_core;
 
 pub mod cache;
 pub mod command_metadata;
@@ -13,5 +15,13 @@ pub mod query;
 pub mod state;
 pub mod store;
 pub mod value;
+pub mod injection;
 pub mod interpreter;
-pub mod context;
\ No newline at end of file
+pub mod context;
+pub mod serializer;
+pub mod thread_safety;
+pub mod value_enum;
+
+// Used by the code generated by value_enum!
+#[doc(hidden)]
+pub use serde_json;
@@ -115,6 +115,23 @@ impl Default for LogEntry {
     }
 }
 
+/// Dependency of a result on a store key or on another query
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Dependency {
+    Key(#[serde(with = "key_format")] Key),
+    Query(#[serde(with = "query_format")] Query),
+}
+
+/// Timing of a query dependency evaluation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DependencyTiming {
+    #[serde(with = "query_format")]
+    pub query: Query,
+    pub started: String,
+    pub finished: String,
+    pub duration_ms: Option<i64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct MetadataRecord {
     pub log: Vec<LogEntry>,
@@ -128,6 +145,27 @@ pub struct MetadataRecord {
     pub is_error: bool,
     pub media_type: String,
     pub filename: Option<String>,
+    /// Format (extension) of the serialized data
+    #[serde(default)]
+    pub data_format: Option<String>,
+    /// Size of the stored data in bytes
+    #[serde(default)]
+    pub file_size: Option<u64>,
+    /// Time when the evaluation started
+    #[serde(default)]
+    pub started: String,
+    /// Time when the evaluation finished
+    #[serde(default)]
+    pub finished: String,
+    /// Duration of the evaluation in milliseconds
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+    /// Store keys and queries the result depends on
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+    /// Timing of the evaluated query dependencies
+    #[serde(default)]
+    pub dependency_timings: Vec<DependencyTiming>,
 }
 
 mod query_format {
@@ -279,13 +317,57 @@ impl MetadataRecord {
         self.media_type = media_type;
         self
     }
+    pub fn with_data_format(&mut self, data_format: String) -> &mut Self {
+        self.data_format = Some(data_format);
+        self
+    }
+    /// Add a dependency unless it is already present
+    pub fn add_dependency(&mut self, dependency: Dependency) -> &mut Self {
+        if !self.dependencies.contains(&dependency) {
+            self.dependencies.push(dependency);
+        }
+        self
+    }
+    /// Record the timing of an evaluated dependency from its metadata
+    pub fn add_dependency_timing(&mut self, query: Query, metadata: &Metadata) -> &mut Self {
+        if let Metadata::MetadataRecord(m) = metadata {
+            self.dependency_timings.push(DependencyTiming {
+                query,
+                started: m.started.clone(),
+                finished: m.finished.clone(),
+                duration_ms: m.duration_ms,
+            });
+        }
+        self
+    }
+    /// Record the start of the evaluation: sets the start time and the Evaluation status
+    pub fn start_evaluation(&mut self) -> &mut Self {
+        self.started = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        self.with_status(Status::Evaluation)
+    }
+    /// Record the end of the evaluation: sets the finish time, the duration
+    /// and the Ready status (unless the evaluation failed)
+    pub fn finish_evaluation(&mut self) -> &mut Self {
+        let now = chrono::Utc::now();
+        self.finished = now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        if let Ok(started) = chrono::DateTime::parse_from_rfc3339(&self.started) {
+            self.duration_ms = Some((now - started.with_timezone(&chrono::Utc)).num_milliseconds());
+        }
+        if self.status != Status::Error {
+            self.with_status(Status::Ready);
+        }
+        self
+    }
     pub fn add_log_entry(&mut self, log_entry: LogEntry) -> &mut Self {
         self.log.push(log_entry);
         self
     }
+    /// Set the filename; media type is set from the filename extension if known
     pub fn with_filename(&mut self, filename: String) -> &mut Self {
         self.filename = Some(filename);
-        //TODO: set media_type from filename extension
+        if let Some(media_type) = self.extension().and_then(|e| media_type_from_extension(&e)) {
+            self.media_type = media_type;
+        }
         self
     }
     pub fn clean_log(&mut self) -> &mut Self {
@@ -308,6 +390,14 @@ impl MetadataRecord {
         self.add_log_entry(LogEntry::error(message.to_owned()));
         self
     }
+    /// Record an error: sets the status to Error, the message and adds an error log entry
+    pub fn with_error(&mut self, error: &Error) -> &mut Self {
+        let mut entry = LogEntry::error(error.message.clone());
+        entry.with_position(error.position.clone());
+        self.add_log_entry(entry);
+        self.with_message(error.to_string());
+        self.with_status(Status::Error)
+    }
     pub fn type_identifier(&self) -> String {
         self.type_identifier.to_string()
     }
@@ -355,6 +445,18 @@ impl Metadata {
         Metadata::MetadataRecord(MetadataRecord::new())
     }
 
+    /// New metadata record replacing legacy metadata which is not an object (e.g. null or an array).
+    /// Non-empty legacy metadata are mentioned in the log.
+    fn unsupported_legacy_to_record(&self) -> MetadataRecord {
+        let mut m = MetadataRecord::new();
+        if let Metadata::LegacyMetadata(v) = self {
+            if !v.is_null() {
+                m.warning(&format!("Unsupported legacy metadata replaced: {}", v));
+            }
+        }
+        m
+    }
+
     pub fn with_query(&mut self, query: Query) -> &mut Self {
         match self {
             Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
@@ -365,15 +467,72 @@ impl Metadata {
                 m.with_query(query);
                 self
             }
-            Metadata::LegacyMetadata(serde_json::Value::Null) => {
-                let mut m = MetadataRecord::new();
+            Metadata::LegacyMetadata(_) => {
+                let mut m = self.unsupported_legacy_to_record();
                 m.query = query;
                 *self = Metadata::MetadataRecord(m);
                 self
             }
+        }
+    }
 
-            _ => {
-                panic!("Cannot set query on unsupported legacy metadata")
+    pub fn with_key(&mut self, key: Key) -> &mut Self {
+        match self {
+            Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
+                o.insert("key".to_string(), Value::String(key.encode()));
+                self
+            }
+            Metadata::MetadataRecord(m) => {
+                m.with_key(key);
+                self
+            }
+            Metadata::LegacyMetadata(_) => {
+                let mut m = self.unsupported_legacy_to_record();
+                m.key = Some(key);
+                *self = Metadata::MetadataRecord(m);
+                self
+            }
+        }
+    }
+
+    pub fn with_filename(&mut self, filename: String) -> &mut Self {
+        match self {
+            Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
+                let extension = filename.split('.').last().unwrap_or("").to_string();
+                if let Some(media_type) = media_type_from_extension(&extension) {
+                    o.insert("media_type".to_string(), Value::String(media_type));
+                }
+                o.insert("filename".to_string(), Value::String(filename));
+                self
+            }
+            Metadata::MetadataRecord(m) => {
+                m.with_filename(filename);
+                self
+            }
+            Metadata::LegacyMetadata(_) => {
+                let mut m = self.unsupported_legacy_to_record();
+                m.with_filename(filename);
+                *self = Metadata::MetadataRecord(m);
+                self
+            }
+        }
+    }
+
+    pub fn with_data_format(&mut self, data_format: String) -> &mut Self {
+        match self {
+            Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
+                o.insert("data_format".to_string(), Value::String(data_format));
+                self
+            }
+            Metadata::MetadataRecord(m) => {
+                m.with_data_format(data_format);
+                self
+            }
+            Metadata::LegacyMetadata(_) => {
+                let mut m = self.unsupported_legacy_to_record();
+                m.data_format = Some(data_format);
+                *self = Metadata::MetadataRecord(m);
+                self
             }
         }
     }
@@ -405,13 +564,20 @@ impl Metadata {
         }
     }
 
+    pub fn to_json_value(&self) -> serde_json::Result<serde_json::Value> {
+        match self {
+            Metadata::LegacyMetadata(v) => Ok(v.clone()),
+            Metadata::MetadataRecord(m) => serde_json::to_value(m),
+        }
+    }
+
     pub fn get_media_type(&self) -> String {
         match self {
             Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
-                if let Some(mimetype) = o.get("mimetype") {
+                if let Some(Value::String(mimetype)) = o.get("mimetype") {
                     return mimetype.to_string();
                 }
-                if let Some(media_type) = o.get("media_type") {
+                if let Some(Value::String(media_type)) = o.get("media_type") {
                     return media_type.to_string();
                 }
                 return "application/octet-stream".to_string();
@@ -421,6 +587,33 @@ impl Metadata {
         }
     }
 
+    /// Format of the serialized data, if known
+    pub fn data_format(&self) -> Option<String> {
+        match self {
+            Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
+                if let Some(Value::String(data_format)) = o.get("data_format") {
+                    Some(data_format.to_string())
+                } else {
+                    None
+                }
+            }
+            Metadata::MetadataRecord(m) => m.data_format.clone(),
+            _ => None,
+        }
+    }
+
+    /// Status of the data; legacy metadata without a valid status have [Status::None]
+    pub fn status(&self) -> Status {
+        match self {
+            Metadata::LegacyMetadata(serde_json::Value::Object(o)) => o
+                .get("status")
+                .and_then(|status| serde_json::from_value(status.clone()).ok())
+                .unwrap_or(Status::None),
+            Metadata::MetadataRecord(m) => m.status,
+            _ => Status::None,
+        }
+    }
+
     pub fn query(&self) -> Result<Query, crate::error::Error> {
         match self {
             Metadata::LegacyMetadata(serde_json::Value::Object(o)) => {
@@ -450,16 +643,12 @@ impl Metadata {
                 m.with_type_identifier(type_identifier);
                 self
             }
-            Metadata::LegacyMetadata(serde_json::Value::Null) => {
-                let mut m = MetadataRecord::new();
+            Metadata::LegacyMetadata(_) => {
+                let mut m = self.unsupported_legacy_to_record();
                 m.type_identifier = type_identifier;
                 *self = Metadata::MetadataRecord(m);
                 self
             }
-
-            _ => {
-                panic!("Cannot set type_identifier on unsupported legacy metadata")
-            }
         }
     }
     pub fn type_identifier(&self) -> Result<String, Error> {
@@ -542,3 +731,71 @@ impl From<MetadataRecord> for Metadata {
         Metadata::MetadataRecord(m)
     }
 }
+
+/// Guess the data format (extension) from a media type
+pub fn format_from_media_type(media_type: &str) -> Option<String> {
+    let media_type = media_type.split(';').next().unwrap_or("").trim();
+    let format = match media_type {
+        "application/json" => "json",
+        "text/plain" => "txt",
+        "text/html" => "html",
+        "text/markdown" => "md",
+        "text/csv" => "csv",
+        "application/yaml" | "application/x-yaml" | "text/yaml" => "yaml",
+        "application/toml" => "toml",
+        "application/cbor" => "cbor",
+        "application/msgpack" | "application/x-msgpack" => "msgpack",
+        "application/octet-stream" => "b",
+        _ => return None,
+    };
+    Some(format.to_string())
+}
+
+/// Guess the media type from a data format (filename extension)
+pub fn media_type_from_extension(extension: &str) -> Option<String> {
+    let media_type = match extension {
+        "json" | "ejson" => "application/json",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "cbor" => "application/cbor",
+        "msgpack" => "application/msgpack",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "b" | "bin" => "application/octet-stream",
+        _ => return None,
+    };
+    Some(media_type.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_legacy_metadata() -> Result<(), Error> {
+        let mut metadata = Metadata::from_json("[1, 2]").unwrap();
+        assert!(matches!(metadata, Metadata::LegacyMetadata(_)));
+        metadata
+            .with_key(parse::parse_key("a/b.txt")?)
+            .with_filename("b.txt".to_string())
+            .with_data_format("txt".to_string());
+        if let Metadata::MetadataRecord(m) = &metadata {
+            assert_eq!(m.key.as_ref().map(|k| k.encode()), Some("a/b.txt".to_string()));
+            assert_eq!(m.data_format, Some("txt".to_string()));
+            assert_eq!(m.log.len(), 1);
+        } else {
+            panic!("Legacy metadata should be converted to a metadata record");
+        }
+        assert_eq!(metadata.get_media_type(), "text/plain");
+
+        let mut metadata = Metadata::from_json("\"text\"").unwrap();
+        metadata.with_query(parse::parse_query("a/b")?);
+        assert_eq!(metadata.query()?.encode(), "a/b");
+        Ok(())
+    }
+}
@@ -5,7 +5,7 @@ use nom;
 
 extern crate nom_locate;
 use nom::branch::alt;
-use nom::character::complete::digit1;
+use nom::character::complete::{digit1, hex_digit1};
 use nom::combinator::{eof, not, opt, peek};
 use nom::sequence::{preceded, terminated};
 use nom_locate::LocatedSpan;
@@ -109,6 +109,18 @@ fn negative_number_entity(text: Span) -> IResult<Span, String> {
     let (text, n) = digit1(text)?;
     Ok((text, format!("-{n}")))
 }
+fn unicode_entity(text: Span) -> IResult<Span, String> {
+    let (text, _) = tag("~U")(text)?;
+    let (text, code) = hex_digit1(text)?;
+    let (text, _) = tag("~")(text)?;
+    match u32::from_str_radix(code.fragment(), 16).ok().and_then(char::from_u32) {
+        Some(c) => Ok((text, c.to_string())),
+        None => Err(nom::Err::Error(nom::error::Error::new(
+            text,
+            nom::error::ErrorKind::HexDigit,
+        ))),
+    }
+}
 fn space_entity(text: Span) -> IResult<Span, String> {
     let (text, _) = tag("~.")(text)?;
     Ok((text, " ".to_owned()))
@@ -119,6 +131,7 @@ fn entities(text: Span) -> IResult<Span, String> {
         minus_entity,
         negative_number_entity,
         space_entity,
+        unicode_entity,
         islash_entity,
         slash_entity,
         http_entity,
@@ -1000,4 +1013,57 @@ mod tests {
 
         Ok(())
     }
+    #[test]
+    fn parameter_encoding_round_trip() -> Result<(), Error> {
+        let mut samples: Vec<String> = (0x20u8..0x7f).map(|c| (c as char).to_string()).collect();
+        samples.extend(
+            [
+                "",
+                "-12",
+                "a-1",
+                "--",
+                "~1",
+                "~~",
+                "a b/c",
+                "https://example.com/a?b=1&c=2",
+                "x:1,y=2",
+                "{\"a\":[1,2]}",
+                "~U41~",
+                "čšž ü 日本",
+            ]
+            .iter()
+            .map(|s| s.to_string()),
+        );
+        for sample in samples {
+            let query = Query {
+                segments: vec![QuerySegment::Transform(TransformQuerySegment {
+                    query: vec![ActionRequest::new("cmd".to_owned())
+                        .with_parameters(vec![ActionParameter::new_string(sample.clone())])],
+                    ..Default::default()
+                })],
+                ..Default::default()
+            };
+            let encoded = query.encode();
+            let parsed = parse_query(&encoded)?;
+            assert_eq!(parsed, query, "round trip of {:?} encoded as {}", sample, encoded);
+            assert_eq!(parsed.encode(), encoded);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_encoding_is_stable() -> Result<(), Error> {
+        // Queries as stored in caches and metadata are encoded the same way
+        for q in [
+            "abc-def/xxx-123",
+            "-R/a/b/c.txt/-/dr-x_y-1.5-a+b/hello-world/result.json",
+            "-R/data/x.csv/-/filter-~1-~~x-a~.b",
+        ] {
+            assert_eq!(parse_query(q)?.encode(), q);
+        }
+        // Alternative spellings are recognised as the same query
+        assert_eq!(parse_query("cmd-a~/b")?, parse_query("cmd-a~Ib")?);
+        assert_eq!(parse_query("cmd-~H")?.encode(), "cmd-https~U3a~~I~I");
+        Ok(())
+    }
 }
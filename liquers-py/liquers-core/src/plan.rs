@@ -18,12 +18,27 @@ use crate::query::{
 };
 use crate::value::ValueInterface;
 
+/// Single step of a [Plan]
+/// Resource steps are generated from resource query segments:
+/// a segment with a header name (`-Rname/a/b`) is a named resource,
+/// header parameters select a view of the key: `meta` (`-R-meta/a/b`) the metadata instead of the data,
+/// `raw` the data without deserialization and `dir` the listing of a directory.
+/// The key of a named resource starts with the resource name, i.e. it refers to the `name/a/b` key in the store.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Step {
+    /// Get data from the store, deserialized according to the key extension and the stored metadata
     GetResource(Key),
+    /// Get data from the store as raw bytes
+    GetResourceBinary(Key),
+    /// Get the listing of a directory in the store
+    GetResourceDirectory(Key),
+    /// Get the metadata record of a key in the store as a value
     GetResourceMetadata(Key),
+    /// Get data of a named resource
     GetNamedResource(Key),
+    /// Get the metadata record of a named resource as a value
     GetNamedResourceMetadata(Key),
+    /// Evaluate a query; the input state is ignored
     Evaluate(Query),
     Action {
         realm: String,
@@ -36,6 +51,7 @@ pub enum Step {
     Info(String),
     Warning(String),
     Error(String),
+    /// Nested plan, steps are executed in sequence starting from the input state
     Plan(Plan),
 }
 
@@ -172,7 +188,52 @@ impl<'c> PlanBuilder<'c> {
     }
 
     fn process_resource_query(&mut self, rqs: &ResourceQuerySegment) -> Result<(), Error> {
-        self.plan.steps.push(Step::GetResource(rqs.key.clone()));
+        let mut name = "".to_string();
+        let mut view: Option<&str> = None;
+        if let Some(header) = &rqs.header {
+            name = header.name.clone();
+            for parameter in header.parameters.iter() {
+                let value = parameter.value.as_str();
+                if !["meta", "raw", "dir"].contains(&value) {
+                    return Err(Error::new(
+                        ErrorType::ParameterError,
+                        format!(
+                            "Unknown resource header parameter '{}', expected meta, raw or dir",
+                            value
+                        ),
+                    )
+                    .with_position(&parameter.position));
+                }
+                if let Some(previous) = view {
+                    return Err(Error::new(
+                        ErrorType::ParameterError,
+                        format!(
+                            "Resource header parameters '{}' and '{}' can't be combined",
+                            previous, value
+                        ),
+                    )
+                    .with_position(&parameter.position));
+                }
+                view = Some(value);
+            }
+        }
+        let named = !name.is_empty();
+        let key = if named {
+            let mut key = Key::new().join(&name);
+            key.0.extend(rqs.key.iter().cloned());
+            key
+        } else {
+            rqs.key.clone()
+        };
+        let step = match (view, named) {
+            (Some("meta"), false) => Step::GetResourceMetadata(key),
+            (Some("meta"), true) => Step::GetNamedResourceMetadata(key),
+            (Some("raw"), _) => Step::GetResourceBinary(key),
+            (Some("dir"), _) => Step::GetResourceDirectory(key),
+            (_, false) => Step::GetResource(key),
+            (_, true) => Step::GetNamedResource(key),
+        };
+        self.plan.steps.push(step);
         Ok(())
     }
 
@@ -418,4 +479,23 @@ mod tests {
         );
         print!("");
     }
+
+    #[test]
+    fn resource_steps() -> Result<(), Error> {
+        let cr = command_metadata::CommandMetadataRegistry::new();
+        let build = |q: &str| PlanBuilder::new(parse_query(q).unwrap(), &cr).build();
+        assert!(matches!(&build("-R/a/b")?.steps[0], Step::GetResource(k) if k.encode() == "a/b"));
+        assert!(matches!(&build("-R-meta/a/b")?.steps[0], Step::GetResourceMetadata(k) if k.encode() == "a/b"));
+        assert!(matches!(&build("-R-raw/a/b")?.steps[0], Step::GetResourceBinary(k) if k.encode() == "a/b"));
+        assert!(matches!(&build("-Rdata/a/b")?.steps[0], Step::GetNamedResource(k) if k.encode() == "data/a/b"));
+        assert!(matches!(&build("-Rdata-meta/a/b")?.steps[0], Step::GetNamedResourceMetadata(k) if k.encode() == "data/a/b"));
+        assert!(matches!(&build("-R-dir/a/b")?.steps[0], Step::GetResourceDirectory(k) if k.encode() == "a/b"));
+        assert!(matches!(&build("-Rdata-dir/a")?.steps[0], Step::GetResourceDirectory(k) if k.encode() == "data/a"));
+        let e = build("-R-metadata/a/b").unwrap_err();
+        assert_eq!(e.error_type, ErrorType::ParameterError);
+        assert!(e.message.contains("metadata"));
+        assert_eq!(e.position.offset, 3);
+        assert!(build("-R-meta-raw/a/b").is_err());
+        Ok(())
+    }
 }
@@ -53,8 +53,25 @@ impl Display for Position {
     }
 }
 
+/// Encode a string (e.g. an action parameter) so that it is parsed back as a single token.
+/// Alphanumeric ASCII characters, '_', '+' and '.' are kept; '~', '-', '/' and space use the
+/// short entities (~~, ~_ or ~ before a digit, ~I, ~.), any other character is encoded
+/// by its hexadecimal code point as ~U<hex>~.
 pub fn encode_token<S: AsRef<str>>(text: S) -> String {
-    format!("{}", text.as_ref())
+    let mut encoded = String::new();
+    let mut chars = text.as_ref().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '.' => encoded.push(c),
+            '~' => encoded.push_str("~~"),
+            '-' if chars.peek().map_or(false, |c| c.is_ascii_digit()) => encoded.push('~'),
+            '-' => encoded.push_str("~_"),
+            '/' => encoded.push_str("~I"),
+            ' ' => encoded.push_str("~."),
+            c => encoded.push_str(&format!("~U{:x}~", c as u32)),
+        }
+    }
+    encoded
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -653,6 +670,13 @@ impl Key {
         true
     }
 
+    /// True if any element is ".", ".." or empty, i.e. the key may point outside of its prefix (or the store root).
+    /// Such keys need to be resolved with [Key::to_absolute] before they are used in a store.
+    pub fn has_relative_names(&self) -> bool {
+        self.iter()
+            .any(|x| x.name.is_empty() || x.is_cwd() || x.is_parent())
+    }
+
     /// Append a name as a new element at the end of the key
     pub fn join<S: AsRef<str>>(&self, name: S) -> Self {
         let mut key = self.clone();
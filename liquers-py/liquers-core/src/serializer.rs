@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::metadata;
+use crate::state::State;
+use crate::thread_safety::{MaybeSend, MaybeSync};
+use crate::value::ValueInterface;
+
+/// Serializes values of a type to a data format and back.
+/// Serializers are registered in a [SerializerRegistry] for a type identifier and a format (file extension).
+pub trait ValueSerializer<V: ValueInterface>: MaybeSend + MaybeSync {
+    fn serialize(&self, value: &V, format: &str) -> Result<Vec<u8>, Error>;
+    fn deserialize(&self, data: &[u8], type_identifier: &str, format: &str) -> Result<V, Error>;
+    /// Check that the value can be serialized to the format.
+    /// The default implementation serializes the value and discards the data.
+    fn check_format(&self, value: &V, format: &str) -> Result<(), Error> {
+        self.serialize(value, format).map(|_| ())
+    }
+}
+
+/// Serializer using the built-in [crate::value::DefaultValueSerializer] of the value type
+pub struct DefaultSerializer;
+
+impl<V: ValueInterface> ValueSerializer<V> for DefaultSerializer {
+    fn serialize(&self, value: &V, format: &str) -> Result<Vec<u8>, Error> {
+        value.as_bytes(format)
+    }
+    fn deserialize(&self, data: &[u8], type_identifier: &str, format: &str) -> Result<V, Error> {
+        V::deserialize_from_bytes(data, type_identifier, format)
+    }
+    fn check_format(&self, value: &V, format: &str) -> Result<(), Error> {
+        value.check_format(format)
+    }
+}
+
+/// Serializer defined by a pair of functions
+pub struct FnSerializer<S, D> {
+    serialize: S,
+    deserialize: D,
+}
+
+impl<S, D> FnSerializer<S, D> {
+    pub fn new(serialize: S, deserialize: D) -> Self {
+        FnSerializer {
+            serialize,
+            deserialize,
+        }
+    }
+}
+
+impl<V, S, D> ValueSerializer<V> for FnSerializer<S, D>
+where
+    V: ValueInterface,
+    S: Fn(&V, &str) -> Result<Vec<u8>, Error> + MaybeSend + MaybeSync,
+    D: Fn(&[u8], &str, &str) -> Result<V, Error> + MaybeSend + MaybeSync,
+{
+    fn serialize(&self, value: &V, format: &str) -> Result<Vec<u8>, Error> {
+        (self.serialize)(value, format)
+    }
+    fn deserialize(&self, data: &[u8], type_identifier: &str, format: &str) -> Result<V, Error> {
+        (self.deserialize)(data, type_identifier, format)
+    }
+}
+
+/// Registry of serializers keyed by the type identifier and the data format.
+/// A serializer is looked up for the exact type identifier and format first,
+/// then for any type ([SerializerRegistry::ANY_TYPE]) and the format.
+/// The built-in [crate::value::DefaultValueSerializer] of the value type is used if no serializer is registered.
+/// The registry also maps data formats to media types; unregistered formats use the built-in mapping.
+pub struct SerializerRegistry<V: ValueInterface> {
+    serializers: HashMap<(String, String), Arc<dyn ValueSerializer<V>>>,
+    media_types: HashMap<String, String>,
+    default_serializer: Arc<dyn ValueSerializer<V>>,
+}
+
+impl<V: ValueInterface> Clone for SerializerRegistry<V> {
+    fn clone(&self) -> Self {
+        SerializerRegistry {
+            serializers: self.serializers.clone(),
+            media_types: self.media_types.clone(),
+            default_serializer: self.default_serializer.clone(),
+        }
+    }
+}
+
+impl<V: ValueInterface> Default for SerializerRegistry<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: ValueInterface> SerializerRegistry<V> {
+    /// Type identifier matching values of any type
+    pub const ANY_TYPE: &'static str = "*";
+
+    pub fn new() -> Self {
+        SerializerRegistry {
+            serializers: HashMap::new(),
+            media_types: HashMap::new(),
+            default_serializer: Arc::new(DefaultSerializer),
+        }
+    }
+
+    /// Register a serializer for a type identifier (or [SerializerRegistry::ANY_TYPE]) and a format.
+    /// A previously registered serializer for the same type and format is replaced.
+    pub fn register(
+        &mut self,
+        type_identifier: &str,
+        format: &str,
+        serializer: Arc<dyn ValueSerializer<V>>,
+    ) -> &mut Self {
+        self.serializers
+            .insert((type_identifier.to_owned(), format.to_owned()), serializer);
+        self
+    }
+
+    /// Set the media type of a data format
+    pub fn with_media_type(&mut self, format: &str, media_type: &str) -> &mut Self {
+        self.media_types
+            .insert(format.to_owned(), media_type.to_owned());
+        self
+    }
+
+    /// Serializer for the type identifier and the format
+    pub fn get_serializer(&self, type_identifier: &str, format: &str) -> Arc<dyn ValueSerializer<V>> {
+        self.serializers
+            .get(&(type_identifier.to_owned(), format.to_owned()))
+            .or_else(|| {
+                self.serializers
+                    .get(&(Self::ANY_TYPE.to_owned(), format.to_owned()))
+            })
+            .cloned()
+            .unwrap_or(self.default_serializer.clone())
+    }
+
+    /// Serialize a value to the format
+    pub fn serialize(&self, value: &V, format: &str) -> Result<Vec<u8>, Error> {
+        self.get_serializer(&value.identifier(), format)
+            .serialize(value, format)
+    }
+
+    /// Deserialize a value of the type identified by the type identifier from data in the format
+    pub fn deserialize(&self, data: &[u8], type_identifier: &str, format: &str) -> Result<V, Error> {
+        self.get_serializer(type_identifier, format)
+            .deserialize(data, type_identifier, format)
+    }
+
+    /// Media type of a data format
+    pub fn media_type(&self, format: &str) -> Option<String> {
+        self.media_types
+            .get(format)
+            .cloned()
+            .or_else(|| metadata::media_type_from_extension(format))
+    }
+
+    /// Data format of a media type
+    pub fn format_from_media_type(&self, media_type: &str) -> Option<String> {
+        let media_type = media_type.split(';').next().unwrap_or("").trim();
+        self.media_types
+            .iter()
+            .find(|(_, m)| m.as_str() == media_type)
+            .map(|(format, _)| format.to_owned())
+            .or_else(|| metadata::format_from_media_type(media_type))
+    }
+
+    /// Serialize the data of a state in the format given by [State::data_format]
+    pub fn serialize_state(&self, state: &State<V>) -> Result<Vec<u8>, Error> {
+        let format = state.data_format();
+        self.serialize(&state.data, &format)
+            .map_err(|e| Self::state_error(state, &format, e))
+    }
+
+    /// Check that the data of a state can be serialized in the format given by [State::data_format]
+    /// without serializing it (see [ValueSerializer::check_format])
+    pub fn check_state_format(&self, state: &State<V>) -> Result<(), Error> {
+        let format = state.data_format();
+        self.get_serializer(&state.data.identifier(), &format)
+            .check_format(&state.data, &format)
+            .map_err(|e| Self::state_error(state, &format, e))
+    }
+
+    fn state_error(state: &State<V>, format: &str, e: Error) -> Error {
+        Error::new(
+            e.error_type,
+            format!(
+                "Can't serialize {} to {}: {}",
+                state.data.type_name(),
+                format,
+                e.message
+            ),
+        )
+    }
+
+    /// Media type of the serialized data of a state
+    pub fn state_media_type(&self, state: &State<V>) -> String {
+        self.media_type(&state.data_format())
+            .unwrap_or(state.data.default_media_type().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorType;
+    use crate::value::Value;
+
+    #[test]
+    fn test_serializer_registry() -> Result<(), Error> {
+        let mut registry: SerializerRegistry<Value> = SerializerRegistry::new();
+        assert_eq!(registry.serialize(&Value::from("abc"), "json")?, b"\"abc\"");
+        assert_eq!(registry.media_type("json"), Some("application/json".to_string()));
+
+        registry
+            .register(
+                "text",
+                "txt",
+                Arc::new(FnSerializer::new(
+                    |value: &Value, _format: &str| -> Result<Vec<u8>, Error> {
+                        Ok(value.try_into_string()?.to_uppercase().into_bytes())
+                    },
+                    |data: &[u8], _type_identifier: &str, _format: &str| -> Result<Value, Error> {
+                        Ok(Value::from(String::from_utf8_lossy(data).to_lowercase()))
+                    },
+                )),
+            )
+            .register(
+                SerializerRegistry::<Value>::ANY_TYPE,
+                "rev",
+                Arc::new(FnSerializer::new(
+                    |value: &Value, _format: &str| -> Result<Vec<u8>, Error> {
+                        Ok(value.try_into_string()?.chars().rev().collect::<String>().into_bytes())
+                    },
+                    |_data: &[u8], _type_identifier: &str, format: &str| -> Result<Value, Error> {
+                        Err(Error::not_supported(format!("Can't read {}", format)))
+                    },
+                )),
+            )
+            .with_media_type("rev", "text/x-reversed");
+
+        assert_eq!(registry.serialize(&Value::from("abc"), "txt")?, b"ABC");
+        assert_eq!(registry.deserialize(b"ABC", "text", "txt")?, Value::from("abc"));
+        // Other types use the default serializer
+        assert_eq!(registry.serialize(&Value::I32(12), "txt")?, b"12");
+        assert_eq!(registry.serialize(&Value::I32(12), "rev")?, b"21");
+        assert_eq!(
+            registry.deserialize(b"x", "generic", "rev").unwrap_err().error_type,
+            ErrorType::NotSupported
+        );
+        assert_eq!(registry.media_type("rev"), Some("text/x-reversed".to_string()));
+        assert_eq!(
+            registry.format_from_media_type("text/x-reversed; charset=utf-8"),
+            Some("rev".to_string())
+        );
+        assert_eq!(registry.format_from_media_type("text/plain"), Some("txt".to_string()));
+
+        let mut metadata = metadata::Metadata::new();
+        metadata.with_filename("data.rev".to_string());
+        let state = State::new().with_data(Value::from("abc")).with_metadata(metadata);
+        assert_eq!(registry.serialize_state(&state)?, b"cba");
+        assert_eq!(registry.state_media_type(&state), "text/x-reversed");
+        registry.check_state_format(&state)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_state_format() -> Result<(), Error> {
+        let registry: SerializerRegistry<Value> = SerializerRegistry::new();
+        let state_with_filename = |value: Value, filename: &str| {
+            let mut metadata = metadata::Metadata::new();
+            metadata.with_filename(filename.to_string());
+            State::new().with_data(value).with_metadata(metadata)
+        };
+        registry.check_state_format(&state_with_filename(Value::from("abc"), "data.json"))?;
+        registry.check_state_format(&state_with_filename(Value::from("abc"), "data.txt"))?;
+        let error = registry
+            .check_state_format(&state_with_filename(Value::from("abc"), "data.xyz"))
+            .unwrap_err();
+        assert!(error.message.contains("Can't serialize"));
+        assert!(registry
+            .check_state_format(&state_with_filename(Value::from("abc"), "data.toml"))
+            .is_err());
+        assert!(registry
+            .check_state_format(&state_with_filename(Value::from(f64::NAN), "data.json"))
+            .is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use crate::{metadata::Metadata, value::ValueInterface};
+use crate::error::Error;
+use crate::metadata::Metadata;
+use crate::serializer::SerializerRegistry;
+use crate::value::ValueInterface;
 
 #[derive(Debug)]
 pub struct State<V: ValueInterface> {
@@ -45,6 +48,23 @@ impl<V: ValueInterface> State<V> {
     pub fn is_empty(&self) -> bool {
         (*self.data).is_none()
     }
+    /// Format of the data when serialized:
+    /// extension of the filename in metadata, data format in metadata or the default extension of the value
+    pub fn data_format(&self) -> String {
+        self.metadata
+            .extension()
+            .or_else(|| self.metadata.data_format())
+            .unwrap_or(self.data.default_extension().to_string())
+    }
+    /// Media type of the serialized data (see [SerializerRegistry::state_media_type])
+    pub fn media_type(&self) -> String {
+        SerializerRegistry::new().state_media_type(self)
+    }
+    /// Serialize the data in the format given by [State::data_format] with the default serializers.
+    /// Use [SerializerRegistry::serialize_state] to take the registered serializers into account.
+    pub fn as_bytes(&self) -> Result<Vec<u8>, Error> {
+        SerializerRegistry::new().serialize_state(self)
+    }
 }
 
 impl<V: ValueInterface> Default for State<V> {
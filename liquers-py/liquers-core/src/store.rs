@@ -3,8 +3,10 @@ use std::io::{Read, Write};
 use std::path::PathBuf;
 use thiserror::Error;
 
-use crate::metadata::{Metadata, MetadataRecord};
-use crate::query::Key;
+use crate::metadata::{media_type_from_extension, Metadata, MetadataRecord, Status};
+use crate::parse::parse_query;
+use crate::query::{Key, Query};
+use crate::thread_safety::MaybeSend;
 
 #[derive(Error, Debug)]
 pub enum StoreError {
@@ -16,9 +18,40 @@ pub enum StoreError {
     KeyReadError(Key, String),
     #[error("Error writing key {0}, store {1}")]
     KeyWriteError(Key, String),
+    #[error("Recipe {1} for key {0} has not been evaluated")]
+    RecipeNotEvaluated(Key, Query),
+    #[error("Key {0} points outside of store {1}")]
+    KeyOutsideStore(Key, String),
 }
 
-pub trait Store {
+impl From<StoreError> for crate::error::Error {
+    fn from(e: StoreError) -> Self {
+        use crate::error::ErrorType;
+        let error_type = match &e {
+            StoreError::KeyNotFound(_) => ErrorType::KeyNotFound,
+            StoreError::KeyNotSupported(_, _) => ErrorType::KeyNotSupported,
+            StoreError::KeyReadError(_, _) => ErrorType::KeyReadError,
+            StoreError::KeyWriteError(_, _) => ErrorType::KeyWriteError,
+            StoreError::RecipeNotEvaluated(_, _) => ErrorType::NotAvailable,
+            StoreError::KeyOutsideStore(_, _) => ErrorType::PermissionDenied,
+        };
+        crate::error::Error::new(error_type, e.to_string())
+    }
+}
+
+/// Item of a directory listing, see [Store::listdir_entries]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub key: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub media_type: String,
+    pub status: Status,
+}
+
+/// With the `thread-safe` feature stores are Send, so that environments using them can be shared between threads.
+pub trait Store: MaybeSend {
     /// Get store name
     fn store_name(&self) -> String {
         format!("{} Store", self.key_prefix())
@@ -153,6 +186,41 @@ pub trait Store {
         Ok(keys_deep)
     }
 
+    /// Describe the items inside a directory specified by key.
+    /// Size, media type and status are taken from the metadata (if available).
+    fn listdir_entries(&self, key: &Key) -> Result<Vec<DirectoryEntry>, StoreError> {
+        let mut entries = Vec::new();
+        for item_key in self.listdir_keys(key)? {
+            let is_dir = self.is_dir(&item_key);
+            let metadata = self.get_metadata(&item_key).ok();
+            let record = match &metadata {
+                Some(Metadata::MetadataRecord(m)) => Some(m),
+                _ => None,
+            };
+            let media_type = record
+                .map(|m| m.media_type.clone())
+                .filter(|media_type| !media_type.is_empty())
+                .or_else(|| {
+                    if is_dir {
+                        None
+                    } else {
+                        item_key.extension().and_then(|e| media_type_from_extension(&e))
+                    }
+                })
+                .unwrap_or_default();
+            entries.push(DirectoryEntry {
+                name: item_key.filename().map(|f| f.name.clone()).unwrap_or_default(),
+                key: item_key.encode(),
+                is_dir,
+                size: record.and_then(|m| m.file_size),
+                media_type,
+                status: metadata.map(|m| m.status()).unwrap_or(Status::None),
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
     /// Make a directory
     fn makedir(&self, key: &Key) -> Result<(), StoreError> {
         Err(StoreError::KeyNotSupported(
@@ -239,16 +307,24 @@ impl FileStore {
         }
     }
 
-    pub fn key_to_path(&self, key: &Key) -> PathBuf {
+    /// Path of the file for the key; keys with ".", ".." or empty elements are refused,
+    /// so that the store can't access files outside of its directory.
+    pub fn key_to_path(&self, key: &Key) -> Result<PathBuf, StoreError> {
+        if key.has_relative_names() {
+            return Err(StoreError::KeyOutsideStore(key.to_owned(), self.store_name()));
+        }
         let mut path = self.path.clone();
         path.push(key.to_string());
-        path
+        Ok(path)
     }
 
-    pub fn key_to_path_metadata(&self, key: &Key) -> PathBuf {
+    pub fn key_to_path_metadata(&self, key: &Key) -> Result<PathBuf, StoreError> {
+        if key.has_relative_names() {
+            return Err(StoreError::KeyOutsideStore(key.to_owned(), self.store_name()));
+        }
         let mut path = self.path.clone();
         path.push(format!("{}{}", key, Self::METADATA));
-        path
+        Ok(path)
     }
 }
 
@@ -297,7 +373,7 @@ impl Store for FileStore {
     }
 
     fn get_bytes(&self, key: &Key) -> Result<Vec<u8>, StoreError> {
-        let path = self.key_to_path(key);
+        let path = self.key_to_path(key)?;
         if path.exists() {
             let mut file = File::open(path)
                 .map_err(|_| StoreError::KeyReadError(key.to_owned(), self.store_name()))?;
@@ -311,27 +387,37 @@ impl Store for FileStore {
     }
 
     fn get_metadata(&self, key: &Key) -> Result<Metadata, StoreError> {
-        let path = self.key_to_path_metadata(key);
+        let path = self.key_to_path_metadata(key)?;
+        let file_size = std::fs::metadata(self.key_to_path(key)?)
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len());
         if path.exists() {
             let mut file = File::open(path)
                 .map_err(|_| StoreError::KeyReadError(key.to_owned(), self.store_name()))?;
             let mut buffer = Vec::new();
             file.read_to_end(&mut buffer)
                 .map_err(|_| StoreError::KeyReadError(key.to_owned(), self.store_name()))?;
-            if let Ok(metadata) = serde_json::from_reader(&buffer[..]) {
+            if let Ok(mut metadata) = serde_json::from_reader::<_, MetadataRecord>(&buffer[..]) {
+                metadata.file_size = file_size.or(metadata.file_size);
                 return Ok(Metadata::MetadataRecord(metadata));
             }
             if let Ok(metadata) = serde_json::from_reader(&buffer[..]) {
                 return Ok(Metadata::LegacyMetadata(metadata));
             }
             Err(StoreError::KeyReadError(key.to_owned(), self.store_name()))
+        } else if file_size.is_some() {
+            // Data stored without metadata
+            let mut metadata = self.default_metadata(key, false);
+            metadata.file_size = file_size;
+            Ok(Metadata::MetadataRecord(metadata))
         } else {
             Err(StoreError::KeyNotFound(key.to_owned()))
         }
     }
 
     fn set(&mut self, key: &Key, data: &[u8], metadata: &Metadata) -> Result<(), StoreError> {
-        let path = self.key_to_path(key);
+        let path = self.key_to_path(key)?;
         let mut file = File::create(path)
             .map_err(|_| StoreError::KeyWriteError(key.to_owned(), self.store_name()))?;
         file.write_all(data)
@@ -341,7 +427,7 @@ impl Store for FileStore {
     }
 
     fn set_metadata(&mut self, key: &Key, metadata: &Metadata) -> Result<(), StoreError> {
-        let path = self.key_to_path_metadata(key);
+        let path = self.key_to_path_metadata(key)?;
         let file = File::create(path)
             .map_err(|_| StoreError::KeyWriteError(key.to_owned(), self.store_name()))?;
         match metadata {
@@ -354,12 +440,12 @@ impl Store for FileStore {
     }
 
     fn remove(&mut self, key: &Key) -> Result<(), StoreError> {
-        let path = self.key_to_path(key);
+        let path = self.key_to_path(key)?;
         if path.exists() {
             std::fs::remove_file(path)
                 .map_err(|_| StoreError::KeyWriteError(key.to_owned(), self.store_name()))?;
         }
-        let matadata_path = self.key_to_path_metadata(key);
+        let matadata_path = self.key_to_path_metadata(key)?;
         if matadata_path.exists() {
             std::fs::remove_file(matadata_path)
                 .map_err(|_| StoreError::KeyWriteError(key.to_owned(), self.store_name()))?;
@@ -368,7 +454,7 @@ impl Store for FileStore {
     }
 
     fn removedir(&mut self, key: &Key) -> Result<(), StoreError> {
-        let path = self.key_to_path(key);
+        let path = self.key_to_path(key)?;
         if path.exists() {
             std::fs::remove_dir_all(path)
                 .map_err(|_| StoreError::KeyWriteError(key.to_owned(), self.store_name()))?;
@@ -377,11 +463,15 @@ impl Store for FileStore {
     }
 
     fn contains(&self, key: &Key) -> bool {
-        let path = self.key_to_path(key);
+        let Ok(path) = self.key_to_path(key) else {
+            return false;
+        };
         if path.exists() {
             return true;
         }
-        let metadata_path = self.key_to_path_metadata(key);
+        let Ok(metadata_path) = self.key_to_path_metadata(key) else {
+            return false;
+        };
         if metadata_path.exists() {
             return true;
         }
@@ -389,7 +479,9 @@ impl Store for FileStore {
     }
 
     fn is_dir(&self, key: &Key) -> bool {
-        let path = self.key_to_path(key);
+        let Ok(path) = self.key_to_path(key) else {
+            return false;
+        };
         if path.exists() {
             return path.is_dir();
         }
@@ -397,7 +489,7 @@ impl Store for FileStore {
     }
 
     fn listdir(&self, key: &Key) -> Result<Vec<String>, StoreError> {
-        let path = self.key_to_path(key);
+        let path = self.key_to_path(key)?;
         if path.exists() {
             let dir = path
                 .read_dir()
@@ -416,7 +508,7 @@ impl Store for FileStore {
     }
 
     fn makedir(&self, key: &Key) -> Result<(), StoreError> {
-        let path = self.key_to_path(key);
+        let path = self.key_to_path(key)?;
         std::fs::create_dir_all(path)
             .map_err(|_| StoreError::KeyWriteError(key.to_owned(), self.store_name()))?;
         Ok(())
@@ -440,6 +532,14 @@ impl MemoryStore {
             prefix: prefix.to_owned(),
         }
     }
+
+    fn with_file_size(metadata: &Metadata, data: &[u8]) -> Metadata {
+        let mut metadata = metadata.to_owned();
+        if let Metadata::MetadataRecord(m) = &mut metadata {
+            m.file_size = Some(data.len() as u64);
+        }
+        metadata
+    }
 }
 
 impl Store for MemoryStore {
@@ -480,7 +580,7 @@ impl Store for MemoryStore {
 
     fn get(&self, key: &Key) -> Result<(Vec<u8>, Metadata), StoreError> {
         match self.data.get(key) {
-            Some((data, metadata)) => Ok((data.to_owned(), metadata.to_owned())),
+            Some((data, metadata)) => Ok((data.to_owned(), Self::with_file_size(metadata, data))),
             None => Err(StoreError::KeyNotFound(key.to_owned())),
         }
     }
@@ -494,7 +594,7 @@ impl Store for MemoryStore {
 
     fn get_metadata(&self, key: &Key) -> Result<Metadata, StoreError> {
         match self.data.get(key) {
-            Some((_, metadata)) => Ok(metadata.to_owned()),
+            Some((data, metadata)) => Ok(Self::with_file_size(metadata, data)),
             None => Err(StoreError::KeyNotFound(key.to_owned())),
         }
     }
@@ -548,12 +648,22 @@ impl Store for MemoryStore {
 
     fn listdir(&self, key: &Key) -> Result<Vec<String>, StoreError> {
         let keys = self.listdir_keys(key)?;
-        Ok(keys.iter().map(|x| x.to_string()).collect())
+        Ok(keys
+            .iter()
+            .filter_map(|x| x.filename().map(|name| name.name.clone()))
+            .collect())
     }
 
+    /// Keys in the directory, including the subdirectories implied by the stored keys
     fn listdir_keys(&self, key: &Key) -> Result<Vec<Key>, StoreError> {
         let n = key.len() + 1;
-        let keys = self.data.keys().filter(|k| k.has_key_prefix(key) && k.len()==n).cloned().collect::<Vec<_>>();
+        let mut keys = Vec::new();
+        for k in self.data.keys().filter(|k| k.has_key_prefix(key) && k.len() >= n) {
+            let item = Key(k.0[..n].to_vec());
+            if !keys.contains(&item) {
+                keys.push(item);
+            }
+        }
         Ok(keys)
     }
 
@@ -573,6 +683,274 @@ impl Store for MemoryStore {
     
 }
 
+/// Store wrapper providing recipes - keys with data computed from queries on demand.
+/// Recipes are defined by [RecipeStore::with_recipe] or in a `recipes.yaml` file
+/// (mapping of names to queries) in the directory containing the key.
+/// Until a recipe is computed (or when it is stale, i.e. its query changed or it failed),
+/// `get` and `get_bytes` fail with [StoreError::RecipeNotEvaluated]
+/// and `get_metadata` returns metadata having the [Status::Recipe] status and the recipe query.
+/// The interpreter evaluates such a query and stores the result with the [Status::Ready] status.
+/// Parsed recipe files are cached; the cache is invalidated when a recipe file is written through the RecipeStore.
+pub struct RecipeStore {
+    store: Box<dyn Store>,
+    recipes: std::collections::HashMap<Key, Query>,
+    recipe_files: std::sync::Mutex<std::collections::HashMap<Key, Vec<(Key, Query)>>>,
+}
+
+impl RecipeStore {
+    /// Name of the file defining the recipes in a directory
+    pub const RECIPES_FILENAME: &'static str = "recipes.yaml";
+
+    pub fn new(store: Box<dyn Store>) -> RecipeStore {
+        RecipeStore {
+            store,
+            recipes: std::collections::HashMap::new(),
+            recipe_files: std::sync::Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    /// Define a recipe for the key
+    pub fn with_recipe(&mut self, key: &Key, query: Query) -> &mut Self {
+        self.recipes.insert(key.to_owned(), query);
+        self
+    }
+
+    /// Recipes from the recipes file in a directory, the parsed file is cached
+    fn file_recipes(&self, key: &Key) -> Result<Vec<(Key, Query)>, StoreError> {
+        if let Some(recipes) = self.recipe_files.lock().unwrap().get(key) {
+            return Ok(recipes.clone());
+        }
+        let mut recipes = Vec::new();
+        let recipes_key = key.join(Self::RECIPES_FILENAME);
+        if self.store.contains(&recipes_key) {
+            let data = self.store.get_bytes(&recipes_key)?;
+            let definitions: std::collections::BTreeMap<String, String> =
+                serde_yaml::from_slice(&data).map_err(|_| {
+                    StoreError::KeyReadError(recipes_key.to_owned(), self.store_name())
+                })?;
+            for (name, query) in definitions {
+                let query = parse_query(&query).map_err(|_| {
+                    StoreError::KeyReadError(recipes_key.to_owned(), self.store_name())
+                })?;
+                recipes.push((key.join(name), query));
+            }
+        }
+        self.recipe_files
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), recipes.clone());
+        Ok(recipes)
+    }
+
+    /// Forget the parsed recipe files which may be affected by a change of the key
+    fn invalidate_recipe_files(&self, key: &Key) {
+        let is_recipes_file = key
+            .filename()
+            .map(|f| f.name == Self::RECIPES_FILENAME)
+            .unwrap_or(false);
+        let mut recipe_files = self.recipe_files.lock().unwrap();
+        if is_recipes_file {
+            recipe_files.remove(&key.parent());
+        } else {
+            recipe_files.retain(|directory, _| !directory.has_key_prefix(key));
+        }
+    }
+
+    /// Recipes defined in a directory: explicit recipes and the recipes from the recipes file
+    pub fn recipes(&self, key: &Key) -> Result<Vec<(Key, Query)>, StoreError> {
+        let mut recipes = self.file_recipes(key)?;
+        for (recipe_key, query) in self.recipes.iter() {
+            if recipe_key.len() == key.len() + 1
+                && recipe_key.has_key_prefix(key)
+                && !recipes.iter().any(|(k, _)| k == recipe_key)
+            {
+                recipes.push((recipe_key.to_owned(), query.to_owned()));
+            }
+        }
+        Ok(recipes)
+    }
+
+    /// Recipe query for the key, if there is any
+    pub fn recipe(&self, key: &Key) -> Result<Option<Query>, StoreError> {
+        if let Some(query) = self.recipes.get(key) {
+            return Ok(Some(query.to_owned()));
+        }
+        if key.is_empty() {
+            return Ok(None);
+        }
+        Ok(self
+            .recipes(&key.parent())?
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, query)| query))
+    }
+
+    /// Names of the directories directly below the key which contain explicit recipes
+    fn recipe_directories(&self, key: &Key) -> Vec<String> {
+        let mut names = Vec::new();
+        for recipe_key in self.recipes.keys() {
+            if recipe_key.len() > key.len() + 1 && recipe_key.has_key_prefix(key) {
+                let name = &recipe_key[key.len()].name;
+                if !names.contains(name) {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+        names
+    }
+
+    /// Metadata of a recipe which needs to be (re)computed, None if the stored data is up to date
+    fn stale_recipe(&self, key: &Key) -> Result<Option<Metadata>, StoreError> {
+        if let Some(query) = self.recipe(key)? {
+            if let Ok(metadata) = self.store.get_metadata(key) {
+                let up_to_date = metadata.status() == Status::Ready
+                    && metadata.query().map(|q| q == query).unwrap_or(false);
+                if up_to_date && self.store.contains(key) {
+                    return Ok(None);
+                }
+            }
+            let mut metadata = MetadataRecord::new();
+            metadata
+                .with_key(key.to_owned())
+                .with_query(query)
+                .with_status(Status::Recipe);
+            return Ok(Some(Metadata::MetadataRecord(metadata)));
+        }
+        Ok(None)
+    }
+}
+
+impl Store for RecipeStore {
+    fn store_name(&self) -> String {
+        format!("Recipes in {}", self.store.store_name())
+    }
+
+    fn key_prefix(&self) -> Key {
+        self.store.key_prefix()
+    }
+
+    fn default_metadata(&self, key: &Key, is_dir: bool) -> MetadataRecord {
+        self.store.default_metadata(key, is_dir)
+    }
+
+    fn finalize_metadata(&self, metadata: Metadata, key: &Key, data: &[u8], update: bool) -> Metadata {
+        self.store.finalize_metadata(metadata, key, data, update)
+    }
+
+    fn finalize_metadata_empty(
+        &self,
+        metadata: Metadata,
+        key: &Key,
+        is_dir: bool,
+        update: bool,
+    ) -> Metadata {
+        self.store.finalize_metadata_empty(metadata, key, is_dir, update)
+    }
+
+    fn get(&self, key: &Key) -> Result<(Vec<u8>, Metadata), StoreError> {
+        if let Some(metadata) = self.stale_recipe(key)? {
+            return Err(StoreError::RecipeNotEvaluated(
+                key.to_owned(),
+                metadata.query().unwrap_or_default(),
+            ));
+        }
+        self.store.get(key)
+    }
+
+    fn get_bytes(&self, key: &Key) -> Result<Vec<u8>, StoreError> {
+        if let Some(metadata) = self.stale_recipe(key)? {
+            return Err(StoreError::RecipeNotEvaluated(
+                key.to_owned(),
+                metadata.query().unwrap_or_default(),
+            ));
+        }
+        self.store.get_bytes(key)
+    }
+
+    fn get_metadata(&self, key: &Key) -> Result<Metadata, StoreError> {
+        if let Some(metadata) = self.stale_recipe(key)? {
+            return Ok(metadata);
+        }
+        self.store.get_metadata(key)
+    }
+
+    fn set(&mut self, key: &Key, data: &[u8], metadata: &Metadata) -> Result<(), StoreError> {
+        self.invalidate_recipe_files(key);
+        self.store.set(key, data, metadata)
+    }
+
+    fn set_metadata(&mut self, key: &Key, metadata: &Metadata) -> Result<(), StoreError> {
+        self.store.set_metadata(key, metadata)
+    }
+
+    fn remove(&mut self, key: &Key) -> Result<(), StoreError> {
+        self.invalidate_recipe_files(key);
+        self.store.remove(key)
+    }
+
+    fn removedir(&mut self, key: &Key) -> Result<(), StoreError> {
+        self.invalidate_recipe_files(key);
+        self.store.removedir(key)
+    }
+
+    fn contains(&self, key: &Key) -> bool {
+        self.store.contains(key) || self.recipe(key).map(|q| q.is_some()).unwrap_or(false)
+    }
+
+    /// A key is a directory in the underlying store or a prefix of a recipe key.
+    /// Recipes from a recipes file are in the directory of the file, which exists in the underlying store.
+    fn is_dir(&self, key: &Key) -> bool {
+        self.store.is_dir(key)
+            || self
+                .recipes
+                .keys()
+                .any(|k| k.len() > key.len() && k.has_key_prefix(key))
+    }
+
+    fn keys(&self) -> Result<Vec<Key>, StoreError> {
+        let mut keys = self.store.keys()?;
+        let mut directories: Vec<Key> = keys
+            .iter()
+            .filter(|k| k.filename().map(|f| f.name == Self::RECIPES_FILENAME).unwrap_or(false))
+            .map(|k| k.parent())
+            .collect();
+        directories.extend(self.recipes.keys().map(|k| k.parent()));
+        for directory in directories {
+            for (key, _) in self.recipes(&directory)? {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    fn listdir(&self, key: &Key) -> Result<Vec<String>, StoreError> {
+        let mut names = self.store.listdir(key).unwrap_or_default();
+        for (recipe_key, _) in self.recipes(key)? {
+            if let Some(name) = recipe_key.filename() {
+                if !names.contains(&name.name) {
+                    names.push(name.name.to_owned());
+                }
+            }
+        }
+        for name in self.recipe_directories(key) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    fn makedir(&self, key: &Key) -> Result<(), StoreError> {
+        self.store.makedir(key)
+    }
+
+    fn is_supported(&self, key: &Key) -> bool {
+        self.store.is_supported(key) || self.recipe(key).map(|q| q.is_some()).unwrap_or(false)
+    }
+}
+
 // Unittests
 #[cfg(test)]
 mod tests {
@@ -604,4 +982,155 @@ mod tests {
         store.remove(&key).unwrap();
         assert!(!store.contains(&key));
     }
+
+    #[test]
+    fn test_recipe_store() {
+        let mut memory_store = MemoryStore::new(&Key::new());
+        memory_store
+            .set(
+                &parse_key("dir/recipes.yaml").unwrap(),
+                b"hello.txt: hello/greet-world\n",
+                &Metadata::new(),
+            )
+            .unwrap();
+        let mut store = RecipeStore::new(Box::new(memory_store));
+        store.with_recipe(&parse_key("dir/data.json").unwrap(), parse_query("data").unwrap());
+
+        let key = parse_key("dir/hello.txt").unwrap();
+        assert_eq!(
+            store.recipe(&key).unwrap(),
+            Some(parse_query("hello/greet-world").unwrap())
+        );
+        assert!(store.contains(&key));
+        assert!(store.is_dir(&parse_key("dir").unwrap()));
+        let names = store.listdir(&parse_key("dir").unwrap()).unwrap();
+        assert!(names.contains(&"hello.txt".to_string()));
+        assert!(names.contains(&"data.json".to_string()));
+        assert!(store.keys().unwrap().contains(&key));
+
+        assert!(matches!(
+            store.get(&key),
+            Err(StoreError::RecipeNotEvaluated(k, q)) if k == key && q.encode() == "hello/greet-world"
+        ));
+        assert!(matches!(store.get_bytes(&key), Err(StoreError::RecipeNotEvaluated(_, _))));
+        let metadata = store.get_metadata(&key).unwrap();
+        assert_eq!(metadata.status(), Status::Recipe);
+        assert_eq!(metadata.query().unwrap().encode(), "hello/greet-world");
+
+        let mut metadata = MetadataRecord::new();
+        metadata
+            .with_query(parse_query("hello/greet-world").unwrap())
+            .with_status(Status::Ready);
+        store
+            .set(&key, b"Hello, world", &Metadata::MetadataRecord(metadata))
+            .unwrap();
+        let (data, metadata) = store.get(&key).unwrap();
+        assert_eq!(data, b"Hello, world");
+        assert_eq!(metadata.status(), Status::Ready);
+
+        // Changed recipe makes the stored data stale
+        store.with_recipe(&key, parse_query("hello/greet-everybody").unwrap());
+        assert_eq!(store.get_metadata(&key).unwrap().status(), Status::Recipe);
+
+        // Rewritten recipes file is parsed again
+        let other_key = parse_key("dir/other.txt").unwrap();
+        assert!(!store.contains(&other_key));
+        store
+            .set(
+                &parse_key("dir/recipes.yaml").unwrap(),
+                b"other.txt: hello/greet-other\n",
+                &Metadata::new(),
+            )
+            .unwrap();
+        assert_eq!(
+            store.recipe(&other_key).unwrap(),
+            Some(parse_query("hello/greet-other").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_recipe_store_directories() {
+        let mut store = RecipeStore::new(Box::new(MemoryStore::new(&Key::new())));
+        store.with_recipe(
+            &parse_key("reports/2024/summary.txt").unwrap(),
+            parse_query("summary").unwrap(),
+        );
+        assert!(store.is_dir(&Key::new()));
+        assert!(store.is_dir(&parse_key("reports").unwrap()));
+        assert!(store.is_dir(&parse_key("reports/2024").unwrap()));
+        assert!(!store.is_dir(&parse_key("reports/2024/summary.txt").unwrap()));
+        assert!(!store.is_dir(&parse_key("reports/2023").unwrap()));
+        assert!(!store.is_dir(&parse_key("other").unwrap()));
+        assert_eq!(store.listdir(&Key::new()).unwrap(), vec!["reports".to_string()]);
+        assert_eq!(
+            store.listdir(&parse_key("reports").unwrap()).unwrap(),
+            vec!["2024".to_string()]
+        );
+        assert_eq!(
+            store.listdir(&parse_key("reports/2024").unwrap()).unwrap(),
+            vec!["summary.txt".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_file_store_refuses_relative_keys() {
+        let dir = std::env::temp_dir().join(format!("liquers-file-store-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+        let mut store = FileStore::new(root.to_str().unwrap(), &Key::new());
+        let outside = parse_key("../secret.txt").unwrap();
+        assert!(matches!(store.get_bytes(&outside), Err(StoreError::KeyOutsideStore(_, _))));
+        assert!(!store.contains(&outside));
+        assert!(store
+            .set(&parse_key("../escaped.txt").unwrap(), b"x", &Metadata::new())
+            .is_err());
+        assert!(store.makedir(&parse_key("a/../../b").unwrap()).is_err());
+        assert!(!dir.join("escaped.txt").exists());
+        assert!(!dir.join("b").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_store_listdir() {
+        let mut store = MemoryStore::new(&Key::new());
+        let mut metadata = MetadataRecord::new();
+        metadata
+            .with_media_type("text/plain".to_string())
+            .with_status(Status::Ready);
+        let metadata = Metadata::MetadataRecord(metadata);
+        store.set(&parse_key("a/b.txt").unwrap(), b"hello", &metadata).unwrap();
+        store.set(&parse_key("a/c/d.json").unwrap(), b"{}", &Metadata::new()).unwrap();
+        store.set(&parse_key("e.txt").unwrap(), b"", &Metadata::new()).unwrap();
+
+        let mut names = store.listdir(&parse_key("a").unwrap()).unwrap();
+        names.sort();
+        assert_eq!(names, vec!["b.txt", "c"]);
+        let mut names = store.listdir(&Key::new()).unwrap();
+        names.sort();
+        assert_eq!(names, vec!["a", "e.txt"]);
+
+        let entries = store.listdir_entries(&parse_key("a").unwrap()).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                DirectoryEntry {
+                    name: "b.txt".to_string(),
+                    key: "a/b.txt".to_string(),
+                    is_dir: false,
+                    size: Some(5),
+                    media_type: "text/plain".to_string(),
+                    status: Status::Ready,
+                },
+                DirectoryEntry {
+                    name: "c".to_string(),
+                    key: "a/c".to_string(),
+                    is_dir: true,
+                    size: None,
+                    media_type: "".to_string(),
+                    status: Status::None,
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,72 @@
+//! Thread-safety of environments is controlled by the `thread-safe` feature (enabled by default).
+//! With the feature, values, commands, stores, caches, serializers and hooks must be Send (and Sync),
+//! so that environments referenced by [crate::context::ArcEnvRef] can be shared between threads
+//! and contexts keep their metadata in `Arc<Mutex<...>>`.
+//! Without the feature (e.g. in single-threaded wasm builds) these bounds are not required,
+//! so that non-Send values (like JavaScript handles) can be used, and contexts use `Rc<RefCell<...>>`.
+
+/// Send if the `thread-safe` feature is enabled, implemented by all types otherwise
+#[cfg(feature = "thread-safe")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "thread-safe")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+/// Send if the `thread-safe` feature is enabled, implemented by all types otherwise
+#[cfg(not(feature = "thread-safe"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "thread-safe"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// Sync if the `thread-safe` feature is enabled, implemented by all types otherwise
+#[cfg(feature = "thread-safe")]
+pub trait MaybeSync: Sync {}
+#[cfg(feature = "thread-safe")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+
+/// Sync if the `thread-safe` feature is enabled, implemented by all types otherwise
+#[cfg(not(feature = "thread-safe"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "thread-safe"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Boxed future, Send if the `thread-safe` feature is enabled
+#[cfg(feature = "thread-safe")]
+pub type BoxFuture<'a, T> = futures_util::future::BoxFuture<'a, T>;
+
+/// Boxed future, Send if the `thread-safe` feature is enabled
+#[cfg(not(feature = "thread-safe"))]
+pub type BoxFuture<'a, T> = futures_util::future::LocalBoxFuture<'a, T>;
+
+/// Shared mutable value: `Arc<Mutex<T>>` with the `thread-safe` feature, `Rc<RefCell<T>>` otherwise
+#[cfg(feature = "thread-safe")]
+pub(crate) struct SharedCell<T>(std::sync::Arc<std::sync::Mutex<T>>);
+
+#[cfg(feature = "thread-safe")]
+impl<T> SharedCell<T> {
+    pub fn new(value: T) -> Self {
+        SharedCell(std::sync::Arc::new(std::sync::Mutex::new(value)))
+    }
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, T> {
+        self.0.lock().unwrap()
+    }
+}
+
+/// Shared mutable value: `Arc<Mutex<T>>` with the `thread-safe` feature, `Rc<RefCell<T>>` otherwise
+#[cfg(not(feature = "thread-safe"))]
+pub(crate) struct SharedCell<T>(std::rc::Rc<std::cell::RefCell<T>>);
+
+#[cfg(not(feature = "thread-safe"))]
+impl<T> SharedCell<T> {
+    pub fn new(value: T) -> Self {
+        SharedCell(std::rc::Rc::new(std::cell::RefCell::new(value)))
+    }
+    pub fn lock(&self) -> std::cell::RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+}
+
+impl<T> Clone for SharedCell<T> {
+    fn clone(&self) -> Self {
+        SharedCell(self.0.clone())
+    }
+}
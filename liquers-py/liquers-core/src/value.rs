@@ -6,6 +6,7 @@ use serde_json;
 use std::{borrow::Cow, collections::BTreeMap, result::Result};
 
 use crate::error::{Error, ErrorType};
+use crate::thread_safety::{MaybeSend, MaybeSync};
 use std::convert::{TryFrom, TryInto};
 
 /// Basic built-in value type
@@ -26,10 +27,41 @@ pub enum Value {
     Bytes(Vec<u8>),
 }
 
+/// Parse an i32 from a string
+/// The parse_* functions define the string representation of the basic types
+/// shared by the [ValueInterface] from_*_str methods and the command parameters.
+pub fn parse_i32(n: &str) -> Result<i32, Error> {
+    n.parse::<i32>()
+        .map_err(|_| Error::conversion_error(n, "i32"))
+}
+
+/// Parse an i64 from a string
+pub fn parse_i64(n: &str) -> Result<i64, Error> {
+    n.parse::<i64>()
+        .map_err(|_| Error::conversion_error(n, "i64"))
+}
+
+/// Parse a f64 from a string
+pub fn parse_f64(n: &str) -> Result<f64, Error> {
+    n.parse::<f64>()
+        .map_err(|_| Error::conversion_error(n, "f64"))
+}
+
+/// Parse a boolean from a string
+/// Accepts true/t/yes/y/1 and false/f/no/n/0 (case insensitive)
+pub fn parse_bool(b: &str) -> Result<bool, Error> {
+    match b.to_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Ok(true),
+        "false" | "f" | "no" | "n" | "0" => Ok(false),
+        _ => Err(Error::conversion_error(b, "bool")),
+    }
+}
+
 /// ValueInterface is a trait that must be implemented by the value type.
 /// This is a central trait that defines the minimum set of operations
 /// that must be supported by the value type.
-pub trait ValueInterface: core::fmt::Debug + Clone + Sized + DefaultValueSerializer{
+/// With the `thread-safe` feature values must be Send and Sync.
+pub trait ValueInterface: core::fmt::Debug + Clone + Sized + MaybeSend + MaybeSync + DefaultValueSerializer{
     /// Empty value
     fn none() -> Self;
 
@@ -47,9 +79,7 @@ pub trait ValueInterface: core::fmt::Debug + Clone + Sized + DefaultValueSeriali
 
     /// From integer string
     fn from_i32_str(n: &str) -> Result<Self, Error> {
-        n.parse::<i32>()
-            .map(|x| Self::from_i32(x))
-            .map_err(|_| Error::conversion_error(n, "i32"))
+        parse_i32(n).map(|x| Self::from_i32(x))
     }
 
     /// From integer
@@ -57,9 +87,7 @@ pub trait ValueInterface: core::fmt::Debug + Clone + Sized + DefaultValueSeriali
 
     /// From integer string
     fn from_i64_str(n: &str) -> Result<Self, Error> {
-        n.parse::<i64>()
-            .map(|x| Self::from_i64(x))
-            .map_err(|_| Error::conversion_error(n, "i64"))
+        parse_i64(n).map(|x| Self::from_i64(x))
     }
 
     /// From float
@@ -67,9 +95,7 @@ pub trait ValueInterface: core::fmt::Debug + Clone + Sized + DefaultValueSeriali
 
     /// From float string
     fn from_f64_str(n: &str) -> Result<Self, Error> {
-        n.parse::<f64>()
-            .map(|x| Self::from_f64(x))
-            .map_err(|_| Error::conversion_error(n, "f64"))
+        parse_f64(n).map(|x| Self::from_f64(x))
     }
 
     /// From boolean
@@ -77,19 +103,7 @@ pub trait ValueInterface: core::fmt::Debug + Clone + Sized + DefaultValueSeriali
 
     /// From boolean string
     fn from_bool_str(b: &str) -> Result<Self, Error> {
-        match b.to_lowercase().as_str() {
-            "true" => Ok(Self::from_bool(true)),
-            "t" => Ok(Self::from_bool(true)),
-            "yes" => Ok(Self::from_bool(true)),
-            "y" => Ok(Self::from_bool(true)),
-            "1" => Ok(Self::from_bool(true)),
-            "false" => Ok(Self::from_bool(false)),
-            "f" => Ok(Self::from_bool(false)),
-            "no" => Ok(Self::from_bool(false)),
-            "n" => Ok(Self::from_bool(false)),
-            "0" => Ok(Self::from_bool(false)),
-            _ => Err(Error::conversion_error(b, "bool")),
-        }
+        parse_bool(b).map(|x| Self::from_bool(x))
     }
 
     /// From bytes
@@ -107,9 +121,74 @@ pub trait ValueInterface: core::fmt::Debug + Clone + Sized + DefaultValueSeriali
         }
     }
 
-    /// Try to get a string out
+    /// Try to get an i32 out
+    /// Accepts i32 and i64 within the i32 range.
     fn try_into_i32(&self) -> Result<i32, Error>;
 
+    /// Try to get an i64 out
+    /// Accepts integers. The default implementation converts via [ValueInterface::try_into_json_value].
+    fn try_into_i64(&self) -> Result<i64, Error> {
+        match self.try_into_json_value() {
+            Ok(serde_json::Value::Number(n)) if !n.is_f64() => n
+                .as_i64()
+                .ok_or_else(|| Error::conversion_error_with_message(self.type_name(), "i64", "out of range")),
+            _ => Err(Error::conversion_error(self.type_name(), "i64")),
+        }
+    }
+
+    /// Try to get a f64 out
+    /// Accepts integers and floats. The default implementation converts via [ValueInterface::try_into_json_value].
+    fn try_into_f64(&self) -> Result<f64, Error> {
+        match self.try_into_json_value() {
+            Ok(serde_json::Value::Number(n)) => n
+                .as_f64()
+                .ok_or_else(|| Error::conversion_error(self.type_name(), "f64")),
+            _ => Err(Error::conversion_error(self.type_name(), "f64")),
+        }
+    }
+
+    /// Try to get a boolean out
+    /// Accepts booleans and integers (non-zero is true).
+    /// The default implementation converts via [ValueInterface::try_into_json_value].
+    fn try_into_bool(&self) -> Result<bool, Error> {
+        match self.try_into_json_value() {
+            Ok(serde_json::Value::Bool(b)) => Ok(b),
+            Ok(serde_json::Value::Number(n)) if !n.is_f64() => Ok(n.as_f64() != Some(0.0)),
+            _ => Err(Error::conversion_error(self.type_name(), "bool")),
+        }
+    }
+
+    /// Try to get bytes out
+    /// Accepts bytes and text (UTF-8 encoded).
+    /// The default implementation converts via [ValueInterface::try_into_json_value] and only supports text.
+    fn try_into_bytes(&self) -> Result<Vec<u8>, Error> {
+        match self.try_into_json_value() {
+            Ok(serde_json::Value::String(s)) => Ok(s.into_bytes()),
+            _ => Err(Error::conversion_error(self.type_name(), "bytes")),
+        }
+    }
+
+    /// Try to get the items of an array out
+    /// The default implementation converts via [ValueInterface::try_into_json_value] and [ValueInterface::try_from_json_value].
+    fn try_into_array(&self) -> Result<Vec<Self>, Error> {
+        match self.try_into_json_value() {
+            Ok(serde_json::Value::Array(a)) => a.iter().map(Self::try_from_json_value).collect(),
+            _ => Err(Error::conversion_error(self.type_name(), "array")),
+        }
+    }
+
+    /// Try to get the entries of an object out
+    /// The default implementation converts via [ValueInterface::try_into_json_value] and [ValueInterface::try_from_json_value].
+    fn try_into_object(&self) -> Result<BTreeMap<String, Self>, Error> {
+        match self.try_into_json_value() {
+            Ok(serde_json::Value::Object(o)) => o
+                .iter()
+                .map(|(k, v)| Ok((k.to_owned(), Self::try_from_json_value(v)?)))
+                .collect(),
+            _ => Err(Error::conversion_error(self.type_name(), "object")),
+        }
+    }
+
     /// String identifier of the state type
     /// Several types can be linked to the same identifier.
     /// The identifier must be cross-platform
@@ -176,21 +255,71 @@ impl ValueInterface for Value {
 
     fn try_into_string(&self) -> Result<String, Error> {
         match self {
+            Value::Bool(b) => Ok(format!("{b}")),
             Value::I32(n) => Ok(format!("{n}")),
             Value::I64(n) => Ok(format!("{n}")),
             Value::F64(n) => Ok(format!("{n}")),
             Value::Text(t) => Ok(t.to_owned()),
-            Value::Bytes(b) => {
-                Ok(String::from_utf8_lossy(b).to_string())
-            },
-            _ => Err(Error::conversion_error(self.identifier(), "string")),
+            Value::Bytes(b) => String::from_utf8(b.to_owned())
+                .map_err(|e| Error::conversion_error_with_message("bytes", "string", &e.to_string())),
+            _ => Err(Error::conversion_error(self.type_name(), "string")),
         }
     }
 
     fn try_into_i32(&self) -> Result<i32, Error> {
         match self {
             Value::I32(n) => Ok(*n),
-            _ => Err(Error::conversion_error(self.identifier(), "i32")),
+            Value::I64(n) => i32::try_from(*n)
+                .map_err(|e| Error::conversion_error_with_message("i64", "i32", &e.to_string())),
+            _ => Err(Error::conversion_error(self.type_name(), "i32")),
+        }
+    }
+
+    fn try_into_i64(&self) -> Result<i64, Error> {
+        match self {
+            Value::I32(n) => Ok(*n as i64),
+            Value::I64(n) => Ok(*n),
+            _ => Err(Error::conversion_error(self.type_name(), "i64")),
+        }
+    }
+
+    fn try_into_f64(&self) -> Result<f64, Error> {
+        match self {
+            Value::I32(n) => Ok(*n as f64),
+            Value::I64(n) => Ok(*n as f64),
+            Value::F64(n) => Ok(*n),
+            _ => Err(Error::conversion_error(self.type_name(), "f64")),
+        }
+    }
+
+    fn try_into_bool(&self) -> Result<bool, Error> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            Value::I32(n) => Ok(*n != 0),
+            Value::I64(n) => Ok(*n != 0),
+            _ => Err(Error::conversion_error(self.type_name(), "bool")),
+        }
+    }
+
+    fn try_into_bytes(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            Value::Bytes(b) => Ok(b.to_owned()),
+            Value::Text(t) => Ok(t.as_bytes().to_vec()),
+            _ => Err(Error::conversion_error(self.type_name(), "bytes")),
+        }
+    }
+
+    fn try_into_array(&self) -> Result<Vec<Self>, Error> {
+        match self {
+            Value::Array(a) => Ok(a.to_owned()),
+            _ => Err(Error::conversion_error(self.type_name(), "array")),
+        }
+    }
+
+    fn try_into_object(&self) -> Result<BTreeMap<String, Self>, Error> {
+        match self {
+            Value::Object(o) => Ok(o.to_owned()),
+            _ => Err(Error::conversion_error(self.type_name(), "object")),
         }
     }
 
@@ -200,9 +329,11 @@ impl ValueInterface for Value {
             Value::Bool(b) => Ok(serde_json::Value::Bool(*b)),
             Value::I32(n) => Ok(serde_json::Value::Number(serde_json::Number::from(*n))),
             Value::I64(n) => Ok(serde_json::Value::Number(serde_json::Number::from(*n))),
-            Value::F64(n) => Ok(serde_json::Value::Number(
-                serde_json::Number::from_f64(*n).unwrap(),
-            )),
+            Value::F64(n) => serde_json::Number::from_f64(*n)
+                .map(serde_json::Value::Number)
+                .ok_or_else(|| {
+                    Error::conversion_error_with_message(n, "JSON value", "non-finite numbers are not supported")
+                }),
             Value::Text(t) => Ok(serde_json::Value::String(t.to_owned())),
             Value::Array(a) => {
                 let mut v = Vec::new();
@@ -348,25 +479,147 @@ impl ValueInterface for Value {
     }
 }
 
+/// Keys of the tagged objects used by the extended JSON encoding
+const EJSON_I64: &str = "$i64";
+const EJSON_F64: &str = "$f64";
+const EJSON_BYTES: &str = "$bytes";
+const EJSON_OBJECT: &str = "$object";
+
+impl Value {
+    /// Convert to the extended JSON representation ("ejson" format), which round-trips every value exactly.
+    /// I32, finite F64 (always with a decimal point), text, bool, none and arrays map to plain JSON.
+    /// I64 is encoded as `{"$i64": "123"}`, non-finite F64 as `{"$f64": "NaN"}` (or `"inf"`, `"-inf"`),
+    /// bytes as `{"$bytes": "<base64>"}`.
+    /// Objects are plain JSON objects unless they could be mistaken for a tag, then they are wrapped as `{"$object": {...}}`.
+    pub fn to_extended_json(&self) -> serde_json::Value {
+        use base64::Engine;
+        fn tagged(tag: &str, value: serde_json::Value) -> serde_json::Value {
+            let mut m = serde_json::Map::new();
+            m.insert(tag.to_owned(), value);
+            serde_json::Value::Object(m)
+        }
+        match self {
+            Value::None => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::I32(n) => serde_json::Value::from(*n),
+            Value::I64(n) => tagged(EJSON_I64, serde_json::Value::String(n.to_string())),
+            Value::F64(n) => match serde_json::Number::from_f64(*n) {
+                Some(x) => serde_json::Value::Number(x),
+                None => {
+                    let x = if n.is_nan() {
+                        "NaN"
+                    } else if *n > 0.0 {
+                        "inf"
+                    } else {
+                        "-inf"
+                    };
+                    tagged(EJSON_F64, serde_json::Value::String(x.to_owned()))
+                }
+            },
+            Value::Text(t) => serde_json::Value::String(t.to_owned()),
+            Value::Array(a) => {
+                serde_json::Value::Array(a.iter().map(|x| x.to_extended_json()).collect())
+            }
+            Value::Object(o) => {
+                let m: serde_json::Map<String, serde_json::Value> = o
+                    .iter()
+                    .map(|(k, v)| (k.to_owned(), v.to_extended_json()))
+                    .collect();
+                if o.len() == 1 && o.keys().all(|k| k.starts_with('$')) {
+                    tagged(EJSON_OBJECT, serde_json::Value::Object(m))
+                } else {
+                    serde_json::Value::Object(m)
+                }
+            }
+            Value::Bytes(b) => tagged(
+                EJSON_BYTES,
+                serde_json::Value::String(base64::engine::general_purpose::STANDARD.encode(b)),
+            ),
+        }
+    }
+
+    /// Convert from the extended JSON representation created by [Value::to_extended_json].
+    /// Plain integers outside of the i32 range are accepted as I64.
+    pub fn from_extended_json(value: &serde_json::Value) -> Result<Value, Error> {
+        use base64::Engine;
+        match value {
+            serde_json::Value::Null => Ok(Value::None),
+            serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
+            serde_json::Value::Number(n) => {
+                if n.is_f64() {
+                    Ok(Value::F64(n.as_f64().unwrap_or(f64::NAN)))
+                } else if let Some(i) = n.as_i64() {
+                    Ok(i32::try_from(i).map(Value::I32).unwrap_or(Value::I64(i)))
+                } else {
+                    Err(Error::conversion_error_with_message(n, "i64", "integer out of range"))
+                }
+            }
+            serde_json::Value::String(s) => Ok(Value::Text(s.to_owned())),
+            serde_json::Value::Array(a) => a
+                .iter()
+                .map(Value::from_extended_json)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            serde_json::Value::Object(o) => {
+                if o.len() == 1 {
+                    let (tag, x) = o.iter().next().unwrap();
+                    match (tag.as_str(), x) {
+                        (EJSON_I64, serde_json::Value::String(s)) => {
+                            return parse_i64(s).map(Value::I64)
+                        }
+                        (EJSON_F64, serde_json::Value::String(s)) => {
+                            return match s.as_str() {
+                                "NaN" => Ok(Value::F64(f64::NAN)),
+                                "inf" => Ok(Value::F64(f64::INFINITY)),
+                                "-inf" => Ok(Value::F64(f64::NEG_INFINITY)),
+                                _ => parse_f64(s).map(Value::F64),
+                            }
+                        }
+                        (EJSON_BYTES, serde_json::Value::String(s)) => {
+                            return base64::engine::general_purpose::STANDARD
+                                .decode(s)
+                                .map(Value::Bytes)
+                                .map_err(|e| {
+                                    Error::conversion_error_with_message("base64", "bytes", &e.to_string())
+                                })
+                        }
+                        (EJSON_OBJECT, serde_json::Value::Object(m)) => {
+                            return m
+                                .iter()
+                                .map(|(k, v)| Ok((k.to_owned(), Value::from_extended_json(v)?)))
+                                .collect::<Result<BTreeMap<_, _>, Error>>()
+                                .map(Value::Object)
+                        }
+                        (EJSON_I64 | EJSON_F64 | EJSON_BYTES | EJSON_OBJECT, _) => {
+                            return Err(Error::conversion_error_with_message(
+                                value,
+                                "value",
+                                &format!("invalid {} tag", tag),
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                o.iter()
+                    .map(|(k, v)| Ok((k.to_owned(), Value::from_extended_json(v)?)))
+                    .collect::<Result<BTreeMap<_, _>, Error>>()
+                    .map(Value::Object)
+            }
+        }
+    }
+}
+
 impl TryFrom<&Value> for i32 {
     type Error = Error;
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::I32(x) => Ok(*x),
-            Value::I64(x) => i32::try_from(*x).map_err(|e| Error::conversion_error_with_message("I64", "i32", &e.to_string())),
-            _ => Err(Error::conversion_error(value.type_name(), "i32")),
-        }
+        value.try_into_i32()
     }
 }
 
 impl TryFrom<Value> for i32 {
     type Error = Error;
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::I32(x) => Ok(x),
-            Value::I64(x) => i32::try_from(x).map_err(|e| Error::conversion_error_with_message("I64", "i32", &e.to_string())),
-            _ => Err(Error::conversion_error(value.type_name(), "i32")),
-        }
+        value.try_into_i32()
     }
 }
 
@@ -385,11 +638,7 @@ impl From<()> for Value {
 impl TryFrom<Value> for i64 {
     type Error = Error;
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::I32(x) => Ok(x as i64),
-            Value::I64(x) => Ok(x),
-            _ => Err(Error::conversion_error(value.type_name(), "i64")),
-        }
+        value.try_into_i64()
     }
 }
 impl From<i64> for Value {
@@ -401,12 +650,7 @@ impl From<i64> for Value {
 impl TryFrom<Value> for f64 {
     type Error = Error;
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::I32(x) => Ok(x as f64),
-            Value::I64(x) => Ok(x as f64),
-            Value::F64(x) => Ok(x),
-            _ => Err(Error::conversion_error(value.type_name(), "f64")),
-        }
+        value.try_into_f64()
     }
 }
 impl From<f64> for Value {
@@ -418,11 +662,7 @@ impl From<f64> for Value {
 impl TryFrom<Value> for bool {
     type Error = Error;
     fn try_from(value: Value) -> Result<Self, Self::Error> {
-        match value {
-            Value::I32(x) => Ok(x != 0),
-            Value::I64(x) => Ok(x != 0),
-            _ => Err(Error::conversion_error(value.type_name(), "bool")),
-        }
+        value.try_into_bool()
     }
 }
 impl From<bool> for Value {
@@ -436,14 +676,59 @@ impl TryFrom<Value> for String {
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value {
             Value::Text(x) => Ok(x),
-            Value::I32(x) => Ok(format!("{}", x)),
-            Value::I64(x) => Ok(format!("{}", x)),
-            Value::F64(x) => Ok(format!("{}", x)),
-            _ => Err(Error::conversion_error(value.type_name(), "string")),
+            _ => value.try_into_string(),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bytes(x) => Ok(x),
+            _ => value.try_into_bytes(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Value {
+        Value::Bytes(value)
+    }
+}
+
+impl TryFrom<Value> for Vec<Value> {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Array(x) => Ok(x),
+            _ => Err(Error::conversion_error(value.type_name(), "array")),
+        }
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Value {
+        Value::Array(value)
+    }
+}
+
+impl TryFrom<Value> for BTreeMap<String, Value> {
+    type Error = Error;
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Object(x) => Ok(x),
+            _ => Err(Error::conversion_error(value.type_name(), "object")),
         }
     }
 }
 
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(value: BTreeMap<String, Value>) -> Value {
+        Value::Object(value)
+    }
+}
+
 impl From<String> for Value {
     fn from(value: String) -> Value {
         Value::Text(value)
@@ -455,21 +740,29 @@ impl From<&str> for Value {
     }
 }
 
-// TODO: Turn this into a separate object to make it configurable
+/// Built-in serialization of a value type.
+/// It is used by the [crate::serializer::SerializerRegistry] for the formats without a registered serializer.
 pub trait DefaultValueSerializer
 where
     Self: Sized,
 {
     fn as_bytes(&self, format: &str) -> Result<Vec<u8>, Error>;
     fn deserialize_from_bytes(b: &[u8], type_identifier:&str, format: &str) -> Result<Self, Error>;
+    /// Check that [DefaultValueSerializer::as_bytes] supports the value and the format, without serializing the value.
+    /// The default implementation serializes the value and discards the data.
+    fn check_format(&self, format: &str) -> Result<(), Error> {
+        self.as_bytes(format).map(|_| ())
+    }
 }
 
 impl DefaultValueSerializer for Value {
     fn as_bytes(&self, format: &str) -> Result<Vec<u8>, Error> {
+        self.check_format(format)?;
         match format {
-            "json" => serde_json::to_vec(self).map_err(|e| {                
+            "json" => serde_json::to_vec(self).map_err(|e| {
                 Error::new(ErrorType::SerializationError, format!("JSON error {}", e))
             }),
+            "ejson" => serde_json::to_vec(&self.to_extended_json()).map_err(|e| serialization_error("JSON", e)),
             "txt" | "html" => match self {
                 Value::None => Ok("none".as_bytes().to_vec()),
                 Value::Bool(true) => Ok("true".as_bytes().to_vec()),
@@ -478,19 +771,61 @@ impl DefaultValueSerializer for Value {
                 Value::I64(x) => Ok(format!("{x}").into_bytes()),
                 Value::F64(x) => Ok(format!("{x}").into_bytes()),
                 Value::Text(x) => Ok(x.as_bytes().to_vec()),
-                _ => Err(Error::new(
-                    ErrorType::SerializationError,
-                    format!(
-                        "Serialization to {} not supported by {}",
-                        format,
-                        self.type_name()
-                    ),
-                )),
+                _ => Err(unsupported_serialization(self, format)),
             },
-            _ => Err(Error::new(
-                ErrorType::SerializationError,
-                format!("Unsupported format {}", format),
-            )),
+            "b" | "bin" => match self {
+                Value::Bytes(x) => Ok(x.clone()),
+                _ => Err(unsupported_serialization(self, format)),
+            },
+            "yaml" | "yml" => serde_yaml::to_string(self)
+                .map(|x| x.into_bytes())
+                .map_err(|e| serialization_error("YAML", e)),
+            "toml" => toml::to_string(self)
+                .map(|x| x.into_bytes())
+                .map_err(|e| serialization_error("TOML", e)),
+            "cbor" => {
+                let mut buffer = Vec::new();
+                ciborium::ser::into_writer(&BinaryValue(self), &mut buffer)
+                    .map_err(|e| serialization_error("CBOR", e))?;
+                Ok(buffer)
+            }
+            "msgpack" => {
+                rmp_serde::to_vec(&BinaryValue(self)).map_err(|e| serialization_error("MessagePack", e))
+            }
+            "csv" => value_to_csv(self),
+            _ => Err(unsupported_format(format)),
+        }
+    }
+    fn check_format(&self, format: &str) -> Result<(), Error> {
+        match format {
+            "json" => check_finite(self, format),
+            "ejson" | "cbor" | "msgpack" => Ok(()),
+            "txt" | "html" => match self {
+                Value::None
+                | Value::Bool(_)
+                | Value::I32(_)
+                | Value::I64(_)
+                | Value::F64(_)
+                | Value::Text(_) => Ok(()),
+                _ => Err(unsupported_serialization(self, format)),
+            },
+            "b" | "bin" => match self {
+                Value::Bytes(_) => Ok(()),
+                _ => Err(unsupported_serialization(self, format)),
+            },
+            "yaml" | "yml" => check_representable(self, format, true, false),
+            "toml" => {
+                if !matches!(self, Value::Object(_)) {
+                    return Err(Error::conversion_error_with_message(
+                        self.type_name(),
+                        format,
+                        "only objects can be stored as TOML",
+                    ));
+                }
+                check_representable(self, format, false, false)
+            }
+            "csv" => csv_columns(self).map(|_| ()),
+            _ => Err(unsupported_format(format)),
         }
     }
     fn deserialize_from_bytes(b: &[u8], _type_identifier:&str, fmt: &str) -> Result<Self, Error> {
@@ -501,14 +836,306 @@ impl DefaultValueSerializer for Value {
                     format!("JSON error in from_bytes:{}", e),
                 )
             }),
-            _ => Err(Error::new(
-                ErrorType::SerializationError,
-                format!("Unsupported format in from_bytes:{}", fmt),
-            )),
+            "txt" | "html" | "md" => String::from_utf8(b.to_vec())
+                .map(Value::Text)
+                .map_err(|e| {
+                    Error::new(
+                        ErrorType::SerializationError,
+                        format!("UTF-8 error in from_bytes:{}", e),
+                    )
+                }),
+            "ejson" => serde_json::from_slice(b)
+                .map_err(|e| serialization_error("JSON", e))
+                .and_then(|x| Value::from_extended_json(&x)),
+            "b" | "bin" => Ok(Value::Bytes(b.to_vec())),
+            "yaml" | "yml" => serde_yaml::from_slice(b).map_err(|e| serialization_error("YAML", e)),
+            "toml" => {
+                let text = std::str::from_utf8(b).map_err(|e| serialization_error("TOML", e))?;
+                toml::from_str(text).map_err(|e| serialization_error("TOML", e))
+            }
+            "cbor" => ciborium::de::from_reader::<OwnedBinaryValue, _>(b)
+                .map(|x| x.0)
+                .map_err(|e| serialization_error("CBOR", e)),
+            "msgpack" => rmp_serde::from_slice::<OwnedBinaryValue>(b)
+                .map(|x| x.0)
+                .map_err(|e| serialization_error("MessagePack", e)),
+            "csv" => csv_to_value(b),
+            _ => Err(Error::not_supported(format!(
+                "Unsupported format in from_bytes:{}",
+                fmt
+            ))),
         }
     }
 }
 
+fn unsupported_serialization(value: &Value, format: &str) -> Error {
+    Error::new(
+        ErrorType::SerializationError,
+        format!(
+            "Serialization to {} not supported by {}",
+            format,
+            value.type_name()
+        ),
+    )
+}
+
+fn unsupported_format(format: &str) -> Error {
+    Error::new(
+        ErrorType::SerializationError,
+        format!("Unsupported format {}", format),
+    )
+}
+
+fn serialization_error<E: std::fmt::Display>(format: &str, e: E) -> Error {
+    Error::new(ErrorType::SerializationError, format!("{} error: {}", format, e))
+}
+
+/// Fails with a conversion error if the value contains data the format can't represent
+fn check_representable(value: &Value, format: &str, allow_none: bool, allow_bytes: bool) -> Result<(), Error> {
+    match value {
+        Value::None if !allow_none => Err(Error::conversion_error_with_message(
+            "none",
+            format,
+            "null values are not supported",
+        )),
+        Value::Bytes(_) if !allow_bytes => Err(Error::conversion_error_with_message(
+            "bytes",
+            format,
+            "binary data are not supported",
+        )),
+        Value::Array(a) => a
+            .iter()
+            .try_for_each(|x| check_representable(x, format, allow_none, allow_bytes)),
+        Value::Object(o) => o
+            .values()
+            .try_for_each(|x| check_representable(x, format, allow_none, allow_bytes)),
+        _ => Ok(()),
+    }
+}
+
+/// Fails with a conversion error if the value contains NaN or infinite numbers,
+/// which plain JSON would silently turn into null
+fn check_finite(value: &Value, format: &str) -> Result<(), Error> {
+    match value {
+        Value::F64(x) if !x.is_finite() => Err(Error::conversion_error_with_message(
+            x,
+            format,
+            "non-finite numbers are not supported",
+        )),
+        Value::Array(a) => a.iter().try_for_each(|x| check_finite(x, format)),
+        Value::Object(o) => o.values().try_for_each(|x| check_finite(x, format)),
+        _ => Ok(()),
+    }
+}
+
+/// Serde adapter for binary formats (CBOR, MessagePack), where bytes are stored as byte strings
+struct BinaryValue<'a>(&'a Value);
+
+impl serde::Serialize for BinaryValue<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeSeq};
+        match self.0 {
+            Value::None => serializer.serialize_unit(),
+            Value::Bool(x) => serializer.serialize_bool(*x),
+            Value::I32(x) => serializer.serialize_i32(*x),
+            Value::I64(x) => serializer.serialize_i64(*x),
+            Value::F64(x) => serializer.serialize_f64(*x),
+            Value::Text(x) => serializer.serialize_str(x),
+            Value::Bytes(x) => serializer.serialize_bytes(x),
+            Value::Array(a) => {
+                let mut seq = serializer.serialize_seq(Some(a.len()))?;
+                for x in a {
+                    seq.serialize_element(&BinaryValue(x))?;
+                }
+                seq.end()
+            }
+            Value::Object(o) => {
+                let mut map = serializer.serialize_map(Some(o.len()))?;
+                for (k, x) in o {
+                    map.serialize_entry(k, &BinaryValue(x))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct OwnedBinaryValue(Value);
+
+impl<'de> serde::Deserialize<'de> for OwnedBinaryValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BinaryValueVisitor).map(OwnedBinaryValue)
+    }
+}
+
+struct BinaryValueVisitor;
+
+impl<'de> serde::de::Visitor<'de> for BinaryValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a value")
+    }
+    fn visit_unit<E: serde::de::Error>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+    fn visit_none<E: serde::de::Error>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+    fn visit_some<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(BinaryValueVisitor)
+    }
+    fn visit_bool<E: serde::de::Error>(self, x: bool) -> Result<Value, E> {
+        Ok(Value::Bool(x))
+    }
+    fn visit_i64<E: serde::de::Error>(self, x: i64) -> Result<Value, E> {
+        Ok(i32::try_from(x).map(Value::I32).unwrap_or(Value::I64(x)))
+    }
+    fn visit_u64<E: serde::de::Error>(self, x: u64) -> Result<Value, E> {
+        i64::try_from(x)
+            .map(|x| self.visit_i64::<E>(x))
+            .unwrap_or_else(|_| Err(E::custom(format!("integer {} out of range", x))))
+    }
+    fn visit_f64<E: serde::de::Error>(self, x: f64) -> Result<Value, E> {
+        Ok(Value::F64(x))
+    }
+    fn visit_str<E: serde::de::Error>(self, x: &str) -> Result<Value, E> {
+        Ok(Value::Text(x.to_owned()))
+    }
+    fn visit_string<E: serde::de::Error>(self, x: String) -> Result<Value, E> {
+        Ok(Value::Text(x))
+    }
+    fn visit_bytes<E: serde::de::Error>(self, x: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(x.to_vec()))
+    }
+    fn visit_byte_buf<E: serde::de::Error>(self, x: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(x))
+    }
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut a = Vec::new();
+        while let Some(OwnedBinaryValue(x)) = seq.next_element()? {
+            a.push(x);
+        }
+        Ok(Value::Array(a))
+    }
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut o = BTreeMap::new();
+        while let Some((k, OwnedBinaryValue(x))) = map.next_entry::<String, OwnedBinaryValue>()? {
+            o.insert(k, x);
+        }
+        Ok(Value::Object(o))
+    }
+}
+
+/// Rows and columns of a value stored as CSV: an array of flat objects.
+/// Columns are the object keys in the order of appearance.
+fn csv_columns(value: &Value) -> Result<(&Vec<Value>, Vec<&String>), Error> {
+    let rows = match value {
+        Value::Array(rows) => rows,
+        _ => {
+            return Err(Error::conversion_error_with_message(
+                value.type_name(),
+                "csv",
+                "only arrays of objects can be stored as CSV",
+            ))
+        }
+    };
+    let mut columns: Vec<&String> = Vec::new();
+    for row in rows {
+        match row {
+            Value::Object(o) => {
+                for (k, x) in o {
+                    if matches!(x, Value::Array(_) | Value::Object(_) | Value::Bytes(_)) {
+                        return Err(Error::conversion_error_with_message(
+                            x.type_name(),
+                            "csv",
+                            &format!("nested value in column {} is not supported", k),
+                        ));
+                    }
+                    if !columns.contains(&k) {
+                        columns.push(k);
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::conversion_error_with_message(
+                    row.type_name(),
+                    "csv",
+                    "rows must be objects",
+                ))
+            }
+        }
+    }
+    Ok((rows, columns))
+}
+
+/// Serialize an array of flat objects as CSV.
+/// Columns are the object keys in the order of appearance, missing values are empty.
+fn value_to_csv(value: &Value) -> Result<Vec<u8>, Error> {
+    let (rows, columns) = csv_columns(value)?;
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(&columns)
+        .map_err(|e| serialization_error("CSV", e))?;
+    for row in rows {
+        if let Value::Object(o) = row {
+            let record = columns.iter().map(|k| match o.get(*k) {
+                None | Some(Value::None) => String::new(),
+                Some(Value::Bool(x)) => x.to_string(),
+                Some(Value::I32(x)) => x.to_string(),
+                Some(Value::I64(x)) => x.to_string(),
+                Some(Value::F64(x)) => x.to_string(),
+                Some(Value::Text(x)) => x.to_owned(),
+                Some(_) => String::new(),
+            });
+            writer
+                .write_record(record)
+                .map_err(|e| serialization_error("CSV", e))?;
+        }
+    }
+    writer
+        .into_inner()
+        .map_err(|e| serialization_error("CSV", e))
+}
+
+/// Read CSV with a header as an array of objects.
+/// Empty cells are none, numbers and booleans are recognized, everything else is text.
+fn csv_to_value(b: &[u8]) -> Result<Value, Error> {
+    let mut reader = csv::Reader::from_reader(b);
+    let columns: Vec<String> = reader
+        .headers()
+        .map_err(|e| serialization_error("CSV", e))?
+        .iter()
+        .map(|x| x.to_owned())
+        .collect();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| serialization_error("CSV", e))?;
+        let row = columns
+            .iter()
+            .zip(record.iter())
+            .map(|(k, x)| {
+                let x = if x.is_empty() {
+                    Value::None
+                } else if let Ok(n) = x.parse::<i32>() {
+                    Value::I32(n)
+                } else if let Ok(n) = x.parse::<i64>() {
+                    Value::I64(n)
+                } else if let Ok(n) = x.parse::<f64>() {
+                    Value::F64(n)
+                } else if x == "true" || x == "false" {
+                    Value::Bool(x == "true")
+                } else {
+                    Value::Text(x.to_owned())
+                };
+                (k.to_owned(), x)
+            })
+            .collect();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -596,4 +1223,291 @@ mod tests {
         }
         Ok(())
     }
+    fn sample_object() -> Value {
+        let mut m = BTreeMap::new();
+        m.insert("a".to_owned(), Value::I32(123));
+        m.insert("b".to_owned(), Value::F64(1.5));
+        m.insert("c".to_owned(), Value::from("text"));
+        m.insert(
+            "d".to_owned(),
+            Value::Array(vec![Value::Bool(true), Value::I64(123456789123456789)]),
+        );
+        Value::Object(m)
+    }
+    #[test]
+    fn test_serialization_formats() -> Result<(), Box<dyn std::error::Error>> {
+        let v = sample_object();
+        for format in ["json", "yaml", "toml", "cbor", "msgpack"] {
+            let b = v.as_bytes(format)?;
+            let w: Value = DefaultValueSerializer::deserialize_from_bytes(&b, "generic", format)?;
+            assert_eq!(w, v, "round trip through {}", format);
+        }
+        let v = Value::Array(vec![Value::None, Value::Bytes(vec![0, 1, 255]), sample_object()]);
+        for format in ["cbor", "msgpack"] {
+            let b = v.as_bytes(format)?;
+            let w: Value = DefaultValueSerializer::deserialize_from_bytes(&b, "generic", format)?;
+            assert_eq!(w, v, "round trip through {}", format);
+        }
+        assert_eq!(
+            v.as_bytes("yaml").unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        assert_eq!(
+            Value::I32(1).as_bytes("toml").unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        let mut m = BTreeMap::new();
+        m.insert("x".to_owned(), Value::None);
+        assert_eq!(
+            Value::Object(m).as_bytes("toml").unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_csv() -> Result<(), Box<dyn std::error::Error>> {
+        let v: Value = serde_json::from_str(
+            r#"[{"name":"a","x":1,"y":1.5},{"name":"b","x":2,"z":true},{"name":"c,d","x":null}]"#,
+        )?;
+        let b = v.as_bytes("csv")?;
+        assert_eq!(
+            std::str::from_utf8(&b)?,
+            "name,x,y,z\na,1,1.5,\nb,2,,true\n\"c,d\",,,\n"
+        );
+        let w: Value = DefaultValueSerializer::deserialize_from_bytes(&b, "generic", "csv")?;
+        let expected: Value = serde_json::from_str(
+            r#"[{"name":"a","x":1,"y":1.5,"z":null},{"name":"b","x":2,"y":null,"z":true},{"name":"c,d","x":null,"y":null,"z":null}]"#,
+        )?;
+        assert_eq!(w, expected);
+
+        let nested: Value = serde_json::from_str(r#"[{"a":[1,2]}]"#)?;
+        assert_eq!(
+            nested.as_bytes("csv").unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        assert_eq!(
+            Value::I32(1).as_bytes("csv").unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_extended_json() -> Result<(), Box<dyn std::error::Error>> {
+        let mut m = BTreeMap::new();
+        m.insert("$i64".to_owned(), Value::I32(1));
+        let v = Value::Array(vec![
+            Value::None,
+            Value::Bool(true),
+            Value::I32(1),
+            Value::I64(1),
+            Value::F64(1.0),
+            Value::F64(f64::INFINITY),
+            Value::F64(f64::NEG_INFINITY),
+            Value::from("text"),
+            Value::Bytes(vec![0, 1, 255]),
+            Value::Object(m),
+            sample_object(),
+        ]);
+        let b = v.as_bytes("ejson")?;
+        assert_eq!(
+            std::str::from_utf8(&b)?,
+            concat!(
+                r#"[null,true,1,{"$i64":"1"},1.0,{"$f64":"inf"},{"$f64":"-inf"},"text","#,
+                r#"{"$bytes":"AAH/"},{"$object":{"$i64":1}},"#,
+                r#"{"a":123,"b":1.5,"c":"text","d":[true,{"$i64":"123456789123456789"}]}]"#
+            )
+        );
+        let w: Value = DefaultValueSerializer::deserialize_from_bytes(&b, "generic", "ejson")?;
+        assert_eq!(w, v);
+
+        let nan = Value::from_extended_json(&Value::F64(f64::NAN).to_extended_json())?;
+        assert!(matches!(nan, Value::F64(x) if x.is_nan()));
+        assert_eq!(
+            Value::from_extended_json(&serde_json::from_str("123456789123456789")?)?,
+            Value::I64(123456789123456789)
+        );
+        assert!(Value::from_extended_json(&serde_json::from_str(r#"{"$bytes":1}"#)?).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_json_non_finite() {
+        assert_eq!(
+            Value::F64(f64::NAN).try_into_json_value().unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        assert_eq!(
+            Value::F64(f64::NAN).as_bytes("json").unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        let v = Value::Array(vec![Value::I32(1), Value::F64(f64::NEG_INFINITY)]);
+        assert_eq!(v.as_bytes("json").unwrap_err().error_type, ErrorType::ConversionError);
+        assert!(v.as_bytes("ejson").is_ok());
+    }
+    /// Custom value type relying on the default conversions of [ValueInterface]
+    #[derive(Debug, Clone, PartialEq)]
+    struct CustomValue(Value);
+
+    impl DefaultValueSerializer for CustomValue {
+        fn as_bytes(&self, format: &str) -> Result<Vec<u8>, Error> {
+            self.0.as_bytes(format)
+        }
+        fn deserialize_from_bytes(b: &[u8], type_identifier: &str, format: &str) -> Result<Self, Error> {
+            Value::deserialize_from_bytes(b, type_identifier, format).map(CustomValue)
+        }
+    }
+
+    impl ValueInterface for CustomValue {
+        fn none() -> Self {
+            CustomValue(Value::None)
+        }
+        fn is_none(&self) -> bool {
+            self.0.is_none()
+        }
+        fn new(txt: &str) -> Self {
+            CustomValue(Value::new(txt))
+        }
+        fn from_string(txt: String) -> Self {
+            CustomValue(Value::from_string(txt))
+        }
+        fn from_i32(n: i32) -> Self {
+            CustomValue(Value::I32(n))
+        }
+        fn from_i64(n: i64) -> Self {
+            CustomValue(Value::I64(n))
+        }
+        fn from_f64(n: f64) -> Self {
+            CustomValue(Value::F64(n))
+        }
+        fn from_bool(b: bool) -> Self {
+            CustomValue(Value::Bool(b))
+        }
+        fn from_bytes(b: Vec<u8>) -> Self {
+            CustomValue(Value::Bytes(b))
+        }
+        fn try_into_string(&self) -> Result<String, Error> {
+            self.0.try_into_string()
+        }
+        fn try_into_i32(&self) -> Result<i32, Error> {
+            self.0.try_into_i32()
+        }
+        fn try_into_bytes(&self) -> Result<Vec<u8>, Error> {
+            self.0.try_into_bytes()
+        }
+        fn identifier(&self) -> Cow<'static, str> {
+            self.0.identifier()
+        }
+        fn type_name(&self) -> Cow<'static, str> {
+            self.0.type_name()
+        }
+        fn default_extension(&self) -> Cow<'static, str> {
+            self.0.default_extension()
+        }
+        fn default_filename(&self) -> Cow<'static, str> {
+            self.0.default_filename()
+        }
+        fn default_media_type(&self) -> Cow<'static, str> {
+            self.0.default_media_type()
+        }
+        fn try_into_json_value(&self) -> Result<serde_json::Value, Error> {
+            self.0.try_into_json_value()
+        }
+    }
+
+    /// Results of all conversions: i32, i64, f64, bool, string, bytes, array length, object length
+    fn conversions<V: ValueInterface>(v: &V) -> Vec<Option<String>> {
+        vec![
+            v.try_into_i32().ok().map(|x| x.to_string()),
+            v.try_into_i64().ok().map(|x| x.to_string()),
+            v.try_into_f64().ok().map(|x| x.to_string()),
+            v.try_into_bool().ok().map(|x| x.to_string()),
+            v.try_into_string().ok(),
+            v.try_into_bytes().ok().map(|x| format!("{:?}", x)),
+            v.try_into_array().ok().map(|x| x.len().to_string()),
+            v.try_into_object().ok().map(|x| x.len().to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_conversion_matrix() -> Result<(), Box<dyn std::error::Error>> {
+        let table: Vec<(Value, [Option<&str>; 8])> = vec![
+            (Value::None, [None, None, None, None, None, None, None, None]),
+            (
+                Value::Bool(true),
+                [None, None, None, Some("true"), Some("true"), None, None, None],
+            ),
+            (
+                Value::I32(0),
+                [Some("0"), Some("0"), Some("0"), Some("false"), Some("0"), None, None, None],
+            ),
+            (
+                Value::I32(1),
+                [Some("1"), Some("1"), Some("1"), Some("true"), Some("1"), None, None, None],
+            ),
+            (
+                Value::I64(12),
+                [Some("12"), Some("12"), Some("12"), Some("true"), Some("12"), None, None, None],
+            ),
+            (
+                Value::I64(5000000000),
+                [
+                    None,
+                    Some("5000000000"),
+                    Some("5000000000"),
+                    Some("true"),
+                    Some("5000000000"),
+                    None,
+                    None,
+                    None,
+                ],
+            ),
+            (
+                Value::F64(1.5),
+                [None, None, Some("1.5"), None, Some("1.5"), None, None, None],
+            ),
+            (
+                Value::from("12"),
+                [None, None, None, None, Some("12"), Some("[49, 50]"), None, None],
+            ),
+            (
+                Value::Bytes(vec![97, 98]),
+                [None, None, None, None, Some("ab"), Some("[97, 98]"), None, None],
+            ),
+            (
+                Value::Bytes(vec![255]),
+                [None, None, None, None, None, Some("[255]"), None, None],
+            ),
+            (
+                Value::Array(vec![Value::I32(1), Value::None]),
+                [None, None, None, None, None, None, Some("2"), None],
+            ),
+            (
+                Value::Object(BTreeMap::from([
+                    ("a".to_owned(), Value::I32(1)),
+                    ("b".to_owned(), Value::from("x")),
+                ])),
+                [None, None, None, None, None, None, None, Some("2")],
+            ),
+        ];
+        for (value, expected) in table {
+            let expected: Vec<Option<String>> =
+                expected.iter().map(|x| x.map(|x| x.to_owned())).collect();
+            assert_eq!(conversions(&value), expected, "conversions of {:?}", value);
+            assert_eq!(
+                conversions(&CustomValue(value.clone())),
+                expected,
+                "default conversions of {:?}",
+                value
+            );
+        }
+
+        assert_eq!(
+            Value::F64(1.5).try_into_i32().unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        assert!(bool::try_from(Value::Bool(true))?);
+        assert_eq!(i32::try_from(Value::I64(3))?, 3);
+        assert_eq!(Vec::<u8>::try_from(Value::from("a"))?, vec![97]);
+        assert_eq!(Vec::<Value>::try_from(Value::from(vec![Value::I32(1)]))?, vec![Value::I32(1)]);
+        Ok(())
+    }
 }
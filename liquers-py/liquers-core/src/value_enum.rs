@@ -0,0 +1,609 @@
+/// Defines a custom value type extending the built-in [crate::value::Value] with additional variants.
+///
+/// The generated enum contains all the variants of [crate::value::Value]
+/// (with arrays and objects holding the new type) followed by the extra variants.
+/// Each extra variant wraps a single type and declares its identifier, type name, file extension and media type.
+/// The macro implements [crate::value::ValueInterface], [crate::value::DefaultValueSerializer],
+/// conversions from and to [crate::value::Value], `From`/`TryFrom` for the wrapped types
+/// and the basic types (i32, i64, f64, bool, String) and [crate::commands::IntoCommandResult],
+/// so that commands can return the wrapped types, arrays and objects of the new type directly.
+///
+/// Built-in variants behave exactly like in [crate::value::Value].
+/// Extra variants have no built-in serialization; serializers for them should be registered
+/// in the [crate::serializer::SerializerRegistry] under their identifier.
+///
+/// ```
+/// use liquers_core::value_enum;
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// pub struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// value_enum! {
+///     #[derive(Debug, Clone, PartialEq)]
+///     pub enum MyValue {
+///         Point(Point) {
+///             identifier: "point",
+///             type_name: "point",
+///             extension: "point",
+///             media_type: "application/x-point",
+///         },
+///     }
+/// }
+///
+/// let v = MyValue::from(Point { x: 1.0, y: 2.0 });
+/// assert_eq!(liquers_core::value::ValueInterface::identifier(&v), "point");
+/// ```
+#[macro_export]
+macro_rules! value_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $variant:ident($ty:ty) {
+                    identifier: $identifier:literal,
+                    type_name: $type_name:literal,
+                    extension: $extension:literal,
+                    media_type: $media_type:literal $(,)?
+                }
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            None,
+            Bool(bool),
+            I32(i32),
+            I64(i64),
+            F64(f64),
+            Text(String),
+            Array(Vec<$name>),
+            Object(std::collections::BTreeMap<String, $name>),
+            Bytes(Vec<u8>),
+            $($variant($ty),)*
+        }
+
+        const _: () = {
+            use std::borrow::Cow;
+            use std::collections::BTreeMap;
+            use std::convert::TryFrom;
+            use $crate::error::{Error, ErrorType};
+            use $crate::value::{DefaultValueSerializer, Value, ValueInterface};
+
+            impl $name {
+                /// Convert to the built-in value type; fails if the value contains an extra variant
+                pub fn try_into_base(&self) -> Result<Value, Error> {
+                    match self {
+                        $name::None => Ok(Value::None),
+                        $name::Bool(x) => Ok(Value::Bool(*x)),
+                        $name::I32(x) => Ok(Value::I32(*x)),
+                        $name::I64(x) => Ok(Value::I64(*x)),
+                        $name::F64(x) => Ok(Value::F64(*x)),
+                        $name::Text(x) => Ok(Value::Text(x.to_owned())),
+                        $name::Array(a) => a
+                            .iter()
+                            .map(|x| x.try_into_base())
+                            .collect::<Result<Vec<_>, _>>()
+                            .map(Value::Array),
+                        $name::Object(o) => o
+                            .iter()
+                            .map(|(k, x)| Ok((k.to_owned(), x.try_into_base()?)))
+                            .collect::<Result<BTreeMap<_, _>, Error>>()
+                            .map(Value::Object),
+                        $name::Bytes(x) => Ok(Value::Bytes(x.to_owned())),
+                        $($name::$variant(_) => Err(Error::conversion_error($type_name, "base value")),)*
+                    }
+                }
+
+                /// Empty built-in value of the same variant, used for the variant metadata
+                fn base_kind(&self) -> Option<Value> {
+                    match self {
+                        $name::None => Some(Value::None),
+                        $name::Bool(_) => Some(Value::Bool(false)),
+                        $name::I32(_) => Some(Value::I32(0)),
+                        $name::I64(_) => Some(Value::I64(0)),
+                        $name::F64(_) => Some(Value::F64(0.0)),
+                        $name::Text(_) => Some(Value::Text(String::new())),
+                        $name::Array(_) => Some(Value::Array(Vec::new())),
+                        $name::Object(_) => Some(Value::Object(BTreeMap::new())),
+                        $name::Bytes(_) => Some(Value::Bytes(Vec::new())),
+                        $($name::$variant(_) => None,)*
+                    }
+                }
+
+                /// Convert a scalar built-in variant with a [ValueInterface] method
+                fn convert_base<T>(
+                    &self,
+                    to: &str,
+                    f: impl Fn(&Value) -> Result<T, Error>,
+                ) -> Result<T, Error> {
+                    match self {
+                        $name::Array(_) | $name::Object(_) $(| $name::$variant(_))* => {
+                            Err(Error::conversion_error(self.type_name(), to))
+                        }
+                        _ => f(&self.try_into_base()?),
+                    }
+                }
+            }
+
+            impl ValueInterface for $name {
+                fn none() -> Self {
+                    $name::None
+                }
+                fn is_none(&self) -> bool {
+                    matches!(self, $name::None)
+                }
+                fn new(txt: &str) -> Self {
+                    $name::Text(txt.to_owned())
+                }
+                fn from_string(txt: String) -> Self {
+                    $name::Text(txt)
+                }
+                fn from_i32(n: i32) -> Self {
+                    $name::I32(n)
+                }
+                fn from_i64(n: i64) -> Self {
+                    $name::I64(n)
+                }
+                fn from_f64(n: f64) -> Self {
+                    $name::F64(n)
+                }
+                fn from_bool(b: bool) -> Self {
+                    $name::Bool(b)
+                }
+                fn from_bytes(b: Vec<u8>) -> Self {
+                    $name::Bytes(b)
+                }
+                fn try_into_string(&self) -> Result<String, Error> {
+                    self.convert_base("string", |x| x.try_into_string())
+                }
+                fn try_into_i32(&self) -> Result<i32, Error> {
+                    self.convert_base("i32", |x| x.try_into_i32())
+                }
+                fn try_into_i64(&self) -> Result<i64, Error> {
+                    self.convert_base("i64", |x| x.try_into_i64())
+                }
+                fn try_into_f64(&self) -> Result<f64, Error> {
+                    self.convert_base("f64", |x| x.try_into_f64())
+                }
+                fn try_into_bool(&self) -> Result<bool, Error> {
+                    self.convert_base("bool", |x| x.try_into_bool())
+                }
+                fn try_into_bytes(&self) -> Result<Vec<u8>, Error> {
+                    self.convert_base("bytes", |x| x.try_into_bytes())
+                }
+                fn try_into_array(&self) -> Result<Vec<Self>, Error> {
+                    match self {
+                        $name::Array(a) => Ok(a.to_owned()),
+                        _ => Err(Error::conversion_error(self.type_name(), "array")),
+                    }
+                }
+                fn try_into_object(&self) -> Result<BTreeMap<String, Self>, Error> {
+                    match self {
+                        $name::Object(o) => Ok(o.to_owned()),
+                        _ => Err(Error::conversion_error(self.type_name(), "object")),
+                    }
+                }
+                fn identifier(&self) -> Cow<'static, str> {
+                    match self {
+                        $($name::$variant(_) => $identifier.into(),)*
+                        _ => self.base_kind().map(|x| x.identifier()).unwrap_or_default(),
+                    }
+                }
+                fn type_name(&self) -> Cow<'static, str> {
+                    match self {
+                        $($name::$variant(_) => $type_name.into(),)*
+                        _ => self.base_kind().map(|x| x.type_name()).unwrap_or_default(),
+                    }
+                }
+                fn default_extension(&self) -> Cow<'static, str> {
+                    match self {
+                        $($name::$variant(_) => $extension.into(),)*
+                        _ => self.base_kind().map(|x| x.default_extension()).unwrap_or_default(),
+                    }
+                }
+                fn default_filename(&self) -> Cow<'static, str> {
+                    match self {
+                        $($name::$variant(_) => concat!("data.", $extension).into(),)*
+                        _ => self.base_kind().map(|x| x.default_filename()).unwrap_or_default(),
+                    }
+                }
+                fn default_media_type(&self) -> Cow<'static, str> {
+                    match self {
+                        $($name::$variant(_) => $media_type.into(),)*
+                        _ => self.base_kind().map(|x| x.default_media_type()).unwrap_or_default(),
+                    }
+                }
+                fn try_into_json_value(&self) -> Result<$crate::serde_json::Value, Error> {
+                    match self {
+                        $name::Array(a) => a
+                            .iter()
+                            .map(|x| x.try_into_json_value())
+                            .collect::<Result<Vec<_>, _>>()
+                            .map($crate::serde_json::Value::Array),
+                        $name::Object(o) => o
+                            .iter()
+                            .map(|(k, x)| Ok((k.to_owned(), x.try_into_json_value()?)))
+                            .collect::<Result<$crate::serde_json::Map<_, _>, Error>>()
+                            .map($crate::serde_json::Value::Object),
+                        $($name::$variant(_) => Err(Error::conversion_error($type_name, "JSON value")),)*
+                        _ => self.try_into_base()?.try_into_json_value(),
+                    }
+                }
+                fn try_from_json_value(value: &$crate::serde_json::Value) -> Result<Self, Error> {
+                    Value::try_from_json_value(value).map($name::from)
+                }
+            }
+
+            impl DefaultValueSerializer for $name {
+                fn as_bytes(&self, format: &str) -> Result<Vec<u8>, Error> {
+                    match self {
+                        $($name::$variant(_) => Err(Error::new(
+                            ErrorType::SerializationError,
+                            format!(
+                                "No built-in serialization of {} to {}; register a serializer for '{}'",
+                                $type_name, format, $identifier
+                            ),
+                        )),)*
+                        _ => self.try_into_base()?.as_bytes(format),
+                    }
+                }
+                fn check_format(&self, format: &str) -> Result<(), Error> {
+                    match self {
+                        $($name::$variant(_) => Err(Error::new(
+                            ErrorType::SerializationError,
+                            format!(
+                                "No built-in serialization of {} to {}; register a serializer for '{}'",
+                                $type_name, format, $identifier
+                            ),
+                        )),)*
+                        _ => self.try_into_base()?.check_format(format),
+                    }
+                }
+                fn deserialize_from_bytes(
+                    b: &[u8],
+                    type_identifier: &str,
+                    format: &str,
+                ) -> Result<Self, Error> {
+                    match type_identifier {
+                        $($identifier => Err(Error::not_supported(format!(
+                            "No built-in deserialization of {} from {}; register a serializer for '{}'",
+                            $type_name, format, $identifier
+                        ))),)*
+                        _ => Value::deserialize_from_bytes(b, type_identifier, format).map($name::from),
+                    }
+                }
+            }
+
+            impl From<Value> for $name {
+                fn from(value: Value) -> Self {
+                    match value {
+                        Value::None => $name::None,
+                        Value::Bool(x) => $name::Bool(x),
+                        Value::I32(x) => $name::I32(x),
+                        Value::I64(x) => $name::I64(x),
+                        Value::F64(x) => $name::F64(x),
+                        Value::Text(x) => $name::Text(x),
+                        Value::Array(a) => $name::Array(a.into_iter().map($name::from).collect()),
+                        Value::Object(o) => {
+                            $name::Object(o.into_iter().map(|(k, x)| (k, $name::from(x))).collect())
+                        }
+                        Value::Bytes(x) => $name::Bytes(x),
+                    }
+                }
+            }
+
+            impl TryFrom<$name> for Value {
+                type Error = Error;
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    match value {
+                        $name::None => Ok(Value::None),
+                        $name::Bool(x) => Ok(Value::Bool(x)),
+                        $name::I32(x) => Ok(Value::I32(x)),
+                        $name::I64(x) => Ok(Value::I64(x)),
+                        $name::F64(x) => Ok(Value::F64(x)),
+                        $name::Text(x) => Ok(Value::Text(x)),
+                        $name::Array(a) => a
+                            .into_iter()
+                            .map(Value::try_from)
+                            .collect::<Result<Vec<_>, _>>()
+                            .map(Value::Array),
+                        $name::Object(o) => o
+                            .into_iter()
+                            .map(|(k, x)| Ok((k, Value::try_from(x)?)))
+                            .collect::<Result<BTreeMap<_, _>, Error>>()
+                            .map(Value::Object),
+                        $name::Bytes(x) => Ok(Value::Bytes(x)),
+                        $($name::$variant(_) => Err(Error::conversion_error($type_name, "base value")),)*
+                    }
+                }
+            }
+
+            $(
+                impl From<$ty> for $name {
+                    fn from(value: $ty) -> Self {
+                        $name::$variant(value)
+                    }
+                }
+
+                impl $crate::commands::IntoCommandResult<$name> for $ty {
+                    fn into_command_result(self) -> Result<$name, Error> {
+                        Ok($name::$variant(self))
+                    }
+                }
+
+                impl TryFrom<$name> for $ty {
+                    type Error = Error;
+                    fn try_from(value: $name) -> Result<Self, Self::Error> {
+                        match value {
+                            $name::$variant(x) => Ok(x),
+                            _ => Err(Error::conversion_error(value.type_name(), $type_name)),
+                        }
+                    }
+                }
+            )*
+
+            impl From<i32> for $name {
+                fn from(value: i32) -> Self {
+                    $name::I32(value)
+                }
+            }
+            impl From<i64> for $name {
+                fn from(value: i64) -> Self {
+                    $name::I64(value)
+                }
+            }
+            impl From<f64> for $name {
+                fn from(value: f64) -> Self {
+                    $name::F64(value)
+                }
+            }
+            impl From<bool> for $name {
+                fn from(value: bool) -> Self {
+                    $name::Bool(value)
+                }
+            }
+            impl From<String> for $name {
+                fn from(value: String) -> Self {
+                    $name::Text(value)
+                }
+            }
+            impl From<&str> for $name {
+                fn from(value: &str) -> Self {
+                    $name::Text(value.to_owned())
+                }
+            }
+            impl From<Vec<u8>> for $name {
+                fn from(value: Vec<u8>) -> Self {
+                    $name::Bytes(value)
+                }
+            }
+            impl From<Vec<$name>> for $name {
+                fn from(value: Vec<$name>) -> Self {
+                    $name::Array(value)
+                }
+            }
+            impl From<BTreeMap<String, $name>> for $name {
+                fn from(value: BTreeMap<String, $name>) -> Self {
+                    $name::Object(value)
+                }
+            }
+            impl $crate::commands::IntoCommandResult<$name> for Vec<$name> {
+                fn into_command_result(self) -> Result<$name, Error> {
+                    Ok($name::Array(self))
+                }
+            }
+            impl $crate::commands::IntoCommandResult<$name> for BTreeMap<String, $name> {
+                fn into_command_result(self) -> Result<$name, Error> {
+                    Ok($name::Object(self))
+                }
+            }
+            impl From<()> for $name {
+                fn from(_value: ()) -> Self {
+                    $name::None
+                }
+            }
+            impl TryFrom<$name> for i32 {
+                type Error = Error;
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    value.try_into_i32()
+                }
+            }
+            impl TryFrom<$name> for i64 {
+                type Error = Error;
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    value.try_into_i64()
+                }
+            }
+            impl TryFrom<$name> for f64 {
+                type Error = Error;
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    value.try_into_f64()
+                }
+            }
+            impl TryFrom<$name> for bool {
+                type Error = Error;
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    value.try_into_bool()
+                }
+            }
+            impl TryFrom<$name> for String {
+                type Error = Error;
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    match value {
+                        $name::Text(x) => Ok(x),
+                        _ => value.try_into_string(),
+                    }
+                }
+            }
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::error::{Error, ErrorType};
+    use crate::serializer::{FnSerializer, SerializerRegistry};
+    use crate::value::{DefaultValueSerializer, Value, ValueInterface};
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    value_enum! {
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum ExtValue {
+            Point(Point) {
+                identifier: "point",
+                type_name: "point",
+                extension: "point",
+                media_type: "application/x-point",
+            },
+            Matrix(Vec<Vec<f64>>) {
+                identifier: "matrix",
+                type_name: "matrix",
+                extension: "mtx",
+                media_type: "application/x-matrix",
+            }
+        }
+    }
+
+    #[test]
+    fn test_value_enum() -> Result<(), Error> {
+        let p = ExtValue::from(Point { x: 1.0, y: 2.0 });
+        assert_eq!(p.identifier(), "point");
+        assert_eq!(p.default_filename(), "data.point");
+        assert_eq!(p.default_media_type(), "application/x-point");
+        assert_eq!(ExtValue::Matrix(vec![]).default_extension(), "mtx");
+        assert_eq!(Point::try_from(p.clone())?, Point { x: 1.0, y: 2.0 });
+        assert_eq!(
+            Point::try_from(ExtValue::I32(1)).unwrap_err().error_type,
+            ErrorType::ConversionError
+        );
+        assert!(p.try_into_string().is_err());
+        assert!(p.try_into_json_value().is_err());
+        assert_eq!(
+            p.as_bytes("json").unwrap_err().error_type,
+            ErrorType::SerializationError
+        );
+
+        // Built-in variants behave like Value
+        let base: Value = serde_json::from_str(r#"{"a":[1,true,"x",1.5]}"#).unwrap();
+        let v = ExtValue::from(base.clone());
+        assert_eq!(v.identifier(), base.identifier());
+        assert_eq!(v.default_media_type(), "application/json");
+        assert_eq!(v.try_into_json_value()?, base.try_into_json_value()?);
+        assert_eq!(Value::try_from(v.clone())?, base);
+        assert_eq!(v.as_bytes("yaml")?, base.as_bytes("yaml")?);
+        assert_eq!(
+            ExtValue::deserialize_from_bytes(&base.as_bytes("json")?, "generic", "json")?,
+            v
+        );
+        assert_eq!(ExtValue::from("abc").default_extension(), "txt");
+        assert_eq!(i64::try_from(ExtValue::I32(3))?, 3);
+        assert!(bool::try_from(ExtValue::Bool(true))?);
+
+        let nested = ExtValue::Array(vec![ExtValue::I32(1), p.clone()]);
+        assert_eq!(nested.try_into_array()?.len(), 2);
+        assert!(Value::try_from(nested).is_err());
+
+        // Extra variants are serialized by registered serializers
+        let mut registry: SerializerRegistry<ExtValue> = SerializerRegistry::new();
+        registry.register(
+            "point",
+            "point",
+            Arc::new(FnSerializer::new(
+                |value: &ExtValue, _format: &str| -> Result<Vec<u8>, Error> {
+                    match value {
+                        ExtValue::Point(p) => Ok(format!("{},{}", p.x, p.y).into_bytes()),
+                        _ => Err(Error::conversion_error(value.type_name(), "point")),
+                    }
+                },
+                |data: &[u8], _type_identifier: &str, _format: &str| -> Result<ExtValue, Error> {
+                    let text = String::from_utf8_lossy(data);
+                    let (x, y) = text.split_once(',').ok_or(Error::conversion_error(&text, "point"))?;
+                    Ok(ExtValue::Point(Point {
+                        x: crate::value::parse_f64(x)?,
+                        y: crate::value::parse_f64(y)?,
+                    }))
+                },
+            )),
+        );
+        let data = registry.serialize(&p, "point")?;
+        assert_eq!(data, b"1,2");
+        assert_eq!(registry.deserialize(&data, "point", "point")?, p);
+        assert_eq!(
+            ExtValue::deserialize_from_bytes(&data, "point", "point")
+                .unwrap_err()
+                .error_type,
+            ErrorType::NotSupported
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_value_enum_commands() -> Result<(), Error> {
+        use crate::commands::Command0;
+        use crate::context::{Environment, SimpleEnvironment};
+        use crate::interpreter::PlanInterpreter;
+
+        let mut env: SimpleEnvironment<ExtValue> = SimpleEnvironment::new();
+        let cr = env.get_mut_command_executor();
+        cr.register_command("point", Command0::from(|| Point { x: 1.0, y: 2.0 }))?;
+        cr.register_command(
+            "points",
+            Command0::from(|| -> Result<Vec<ExtValue>, Error> {
+                Ok(vec![ExtValue::from(Point { x: 0.0, y: 0.0 })])
+            }),
+        )?;
+        let env = env.to_ref();
+        let state = PlanInterpreter::new(env.clone()).evaluate("point")?;
+        assert_eq!(*state.data, ExtValue::Point(Point { x: 1.0, y: 2.0 }));
+        let state = PlanInterpreter::new(env).evaluate("points")?;
+        assert_eq!(state.data.try_into_array()?.len(), 1);
+        Ok(())
+    }
+
+    /// Handle which is not Send, like a JavaScript object in wasm
+    #[cfg(not(feature = "thread-safe"))]
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Handle(std::rc::Rc<String>);
+
+    #[cfg(not(feature = "thread-safe"))]
+    value_enum! {
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum LocalValue {
+            Handle(Handle) {
+                identifier: "handle",
+                type_name: "handle",
+                extension: "handle",
+                media_type: "application/x-handle",
+            }
+        }
+    }
+
+    #[cfg(not(feature = "thread-safe"))]
+    #[test]
+    fn test_non_send_value() -> Result<(), Error> {
+        use crate::commands::Command0;
+        use crate::context::{Environment, SimpleEnvironment};
+        use crate::interpreter::PlanInterpreter;
+
+        let mut env: SimpleEnvironment<LocalValue> = SimpleEnvironment::new();
+        env.get_mut_command_executor().register_command(
+            "handle",
+            Command0::from(|| Handle(std::rc::Rc::new("object".to_string()))),
+        )?;
+        let state = PlanInterpreter::new(env.to_ref()).evaluate("handle")?;
+        assert_eq!(
+            *state.data,
+            LocalValue::Handle(Handle(std::rc::Rc::new("object".to_string())))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+//! Procedural macros for liquers.
+//!
+//! The `#[command]` attribute turns a plain Rust function into a liquers command.
+//! It keeps the function as it is and generates a unit struct named after the function
+//! (e.g. `to_upper` becomes `ToUpperCommand`), which implements `Command`
+//! with a `command_metadata()` derived from the function signature, doc comment and attribute annotations.
+//! The struct has a `register` associated function adding the command to a `CommandRegistry`.
+//!
+//! # Example
+//! ```ignore
+//! use liquers_core::{error::Error, state::State, value::{Value, ValueInterface}};
+//! use liquers_macro::command;
+//!
+//! /// Repeat the text
+//! #[command(namespace = "text", default(count = 2), label(count = "Number of repetitions"))]
+//! fn repeat(state: &State<Value>, count: i32) -> Result<String, Error> {
+//!     Ok(state.data.try_into_string()?.repeat(count as usize))
+//! }
+//!
+//! RepeatCommand::register(&mut command_registry)?;
+//! ```
+//!
+//! Supported attribute annotations:
+//! - `name = "..."`, `namespace = "..."`, `realm = "..."` - command key (defaults: function name, root namespace, main realm),
+//! - `cache = false`, `volatile` - flags of the command metadata,
+//! - `default(arg = value, ...)` - default values; string, integer, float, boolean literals or `None`,
+//! - `label(arg = "...", ...)` - argument labels,
+//! - `gui(arg = TextArea(60, 10), ...)` - gui hints (variants of `ArgumentGUIInfo`),
+//! - `alternatives(arg = ["a", "b"], ...)` - makes the argument an enum argument with the listed alternatives.
+//!
+//! The first argument is treated as the state if its type is `&State<V>`; `V` then determines the value type of the command.
+//! Arguments are taken from the query in order; injected arguments (e.g. `Context`) are excluded from the metadata.
+//!
+//! `#[derive(FromParameter)]` allows a type implementing `serde::Deserialize` (e.g. an enum) to be used as a command argument.
+
+extern crate proc_macro;
+
+use std::collections::BTreeMap;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::meta::ParseNestedMeta;
+use syn::{
+    parse_macro_input, DeriveInput, Expr, ExprArray, FnArg, GenericArgument, ItemFn, Lit, LitBool, LitStr, Pat,
+    PathArguments, ReturnType, Type, UnOp,
+};
+
+/// Annotations of a single argument
+#[derive(Default)]
+struct ArgumentAttributes {
+    default: Option<TokenStream2>,
+    label: Option<String>,
+    gui: Option<Expr>,
+    alternatives: Option<Vec<String>>,
+}
+
+/// Annotations of the command
+struct CommandAttributes {
+    name: Option<String>,
+    namespace: String,
+    realm: String,
+    cache: bool,
+    volatile: bool,
+    arguments: BTreeMap<String, ArgumentAttributes>,
+}
+
+impl CommandAttributes {
+    fn new() -> Self {
+        CommandAttributes {
+            name: None,
+            namespace: "".to_string(),
+            realm: "".to_string(),
+            cache: true,
+            volatile: false,
+            arguments: BTreeMap::new(),
+        }
+    }
+
+    fn argument(&mut self, name: String) -> &mut ArgumentAttributes {
+        self.arguments.entry(name).or_default()
+    }
+
+    fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("namespace") {
+            self.namespace = meta.value()?.parse::<LitStr>()?.value();
+        } else if meta.path.is_ident("realm") {
+            self.realm = meta.value()?.parse::<LitStr>()?.value();
+        } else if meta.path.is_ident("cache") {
+            self.cache = parse_flag(&meta)?;
+        } else if meta.path.is_ident("volatile") {
+            self.volatile = parse_flag(&meta)?;
+        } else if meta.path.is_ident("default") {
+            meta.parse_nested_meta(|inner| {
+                let name = argument_name(&inner)?;
+                let value: Expr = inner.value()?.parse()?;
+                self.argument(name).default = Some(default_value(&value)?);
+                Ok(())
+            })?;
+        } else if meta.path.is_ident("label") {
+            meta.parse_nested_meta(|inner| {
+                let name = argument_name(&inner)?;
+                self.argument(name).label = Some(inner.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            })?;
+        } else if meta.path.is_ident("gui") {
+            meta.parse_nested_meta(|inner| {
+                let name = argument_name(&inner)?;
+                self.argument(name).gui = Some(inner.value()?.parse::<Expr>()?);
+                Ok(())
+            })?;
+        } else if meta.path.is_ident("alternatives") {
+            meta.parse_nested_meta(|inner| {
+                let name = argument_name(&inner)?;
+                let array: ExprArray = inner.value()?.parse()?;
+                let mut alternatives = Vec::new();
+                for element in array.elems.iter() {
+                    match element {
+                        Expr::Lit(syn::ExprLit {
+                            lit: Lit::Str(s), ..
+                        }) => alternatives.push(s.value()),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                element,
+                                "alternative must be a string literal",
+                            ))
+                        }
+                    }
+                }
+                self.argument(name).alternatives = Some(alternatives);
+                Ok(())
+            })?;
+        } else {
+            return Err(meta.error("unsupported command attribute"));
+        }
+        Ok(())
+    }
+}
+
+/// Parses either a bare flag (`volatile`) or a flag with a value (`volatile = true`)
+fn parse_flag(meta: &ParseNestedMeta) -> syn::Result<bool> {
+    if meta.input.peek(syn::Token![=]) {
+        Ok(meta.value()?.parse::<LitBool>()?.value)
+    } else {
+        Ok(true)
+    }
+}
+
+fn argument_name(meta: &ParseNestedMeta) -> syn::Result<String> {
+    meta.path
+        .get_ident()
+        .map(|ident| ident.to_string())
+        .ok_or_else(|| meta.error("argument name expected"))
+}
+
+/// Converts a literal to a DefaultValue constructor
+fn default_value(expr: &Expr) -> syn::Result<TokenStream2> {
+    let dv = quote!(::liquers_core::command_metadata::DefaultValue);
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(s) => Ok(quote!(#dv::from_string(#s))),
+            Lit::Int(i) => {
+                let i = i.base10_parse::<i64>()?;
+                Ok(quote!(#dv::from_integer(#i)))
+            }
+            Lit::Float(f) => {
+                let f = f.base10_parse::<f64>()?;
+                Ok(quote!(#dv::from_float(#f)))
+            }
+            Lit::Bool(b) => {
+                let b = b.value;
+                Ok(quote!(#dv::from_bool(#b)))
+            }
+            _ => Err(syn::Error::new_spanned(expr, "unsupported default value")),
+        },
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => {
+            match &*unary.expr {
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Int(i), ..
+                }) => {
+                    let i = -i.base10_parse::<i64>()?;
+                    Ok(quote!(#dv::from_integer(#i)))
+                }
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Float(f), ..
+                }) => {
+                    let f = -f.base10_parse::<f64>()?;
+                    Ok(quote!(#dv::from_float(#f)))
+                }
+                _ => Err(syn::Error::new_spanned(expr, "unsupported default value")),
+            }
+        }
+        Expr::Path(path) if path.path.is_ident("None") => Ok(quote!(#dv::null())),
+        _ => Err(syn::Error::new_spanned(expr, "unsupported default value")),
+    }
+}
+
+/// Name of the last path segment of a type (e.g. `String` for `std::string::String`)
+fn type_name(ty: &Type) -> Option<String> {
+    if let Type::Path(p) = ty {
+        p.path.segments.last().map(|s| s.ident.to_string())
+    } else {
+        None
+    }
+}
+
+/// First generic type argument of a type (e.g. `T` for `Option<T>`)
+fn inner_type(ty: &Type) -> Option<&Type> {
+    if let Type::Path(p) = ty {
+        if let Some(segment) = p.path.segments.last() {
+            if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                for arg in args.args.iter() {
+                    if let GenericArgument::Type(t) = arg {
+                        return Some(t);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+fn is_integer(name: &str) -> bool {
+    matches!(
+        name,
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+    )
+}
+
+fn is_float(name: &str) -> bool {
+    matches!(name, "f32" | "f64")
+}
+
+/// Value type of the state if the type is `&State<V>`
+fn state_value_type(ty: &Type) -> Option<Type> {
+    if let Type::Reference(r) = ty {
+        if type_name(&r.elem).as_deref() == Some("State") {
+            return inner_type(&r.elem).cloned();
+        }
+    }
+    None
+}
+
+/// ArgumentInfo constructor corresponding to the Rust type of the argument
+fn argument_info(name: &str, ty: &Type) -> TokenStream2 {
+    let ai = quote!(::liquers_core::command_metadata::ArgumentInfo);
+    let tn = type_name(ty).unwrap_or_default();
+    if tn == "String" {
+        quote!(#ai::string_argument(#name))
+    } else if tn == "bool" {
+        quote!(#ai::boolean_argument(#name))
+    } else if is_integer(&tn) {
+        quote!(#ai::integer_argument(#name, false))
+    } else if is_float(&tn) {
+        quote!(#ai::float_argument(#name, false))
+    } else if tn == "Option" {
+        let inner = inner_type(ty).and_then(type_name).unwrap_or_default();
+        if is_integer(&inner) {
+            quote!(#ai::integer_argument(#name, true))
+        } else if is_float(&inner) {
+            quote!(#ai::float_argument(#name, true))
+        } else {
+            quote!({
+                let mut a = #ai::argument(#name);
+                a.with_default_none();
+                a
+            })
+        }
+    } else {
+        quote!(#ai::argument(#name))
+    }
+}
+
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Documentation of the function collected from the doc comments
+fn documentation(item: &ItemFn) -> String {
+    let mut lines = Vec::new();
+    for attr in item.attrs.iter() {
+        if attr.path().is_ident("doc") {
+            if let syn::Meta::NameValue(nv) = &attr.meta {
+                if let Expr::Lit(syn::ExprLit {
+                    lit: Lit::Str(s), ..
+                }) = &nv.value
+                {
+                    let line = s.value();
+                    lines.push(line.strip_prefix(' ').unwrap_or(&line).to_string());
+                }
+            }
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Turns a function into a liquers command, see the crate documentation.
+#[proc_macro_attribute]
+pub fn command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut attributes = CommandAttributes::new();
+    let parser = syn::meta::parser(|meta| attributes.parse(meta));
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as ItemFn);
+    match command_impl(attributes, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn command_impl(mut attributes: CommandAttributes, item: ItemFn) -> syn::Result<TokenStream2> {
+    let lc = quote!(::liquers_core);
+    let sig = &item.sig;
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "generic functions are not supported by #[command]",
+        ));
+    }
+    let fn_name = &sig.ident;
+    let vis = &item.vis;
+    let command_name = attributes.name.clone().unwrap_or(fn_name.to_string());
+    let struct_name = format_ident!("{}Command", to_camel_case(&fn_name.to_string()));
+    let realm = attributes.realm.clone();
+    let namespace = attributes.namespace.clone();
+    let cache = attributes.cache;
+    let volatile = attributes.volatile;
+    let doc = documentation(&item);
+
+    let mut state: Option<(String, Type)> = None;
+    let mut arguments: Vec<(String, Type)> = Vec::new();
+    for (i, input) in sig.inputs.iter().enumerate() {
+        match input {
+            FnArg::Receiver(r) => {
+                return Err(syn::Error::new_spanned(
+                    r,
+                    "methods are not supported by #[command]",
+                ))
+            }
+            FnArg::Typed(pt) => {
+                let name = match &*pt.pat {
+                    Pat::Ident(pi) => pi.ident.to_string(),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &pt.pat,
+                            "argument must be an identifier",
+                        ))
+                    }
+                };
+                match state_value_type(&pt.ty) {
+                    Some(value_type) if i == 0 => state = Some((name, value_type)),
+                    Some(_) => {
+                        return Err(syn::Error::new_spanned(
+                            &pt.ty,
+                            "state must be the first argument",
+                        ))
+                    }
+                    None => arguments.push((name, (*pt.ty).clone())),
+                }
+            }
+        }
+    }
+    for name in attributes.arguments.keys() {
+        if !arguments.iter().any(|(n, _)| n == name) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("unknown argument '{}' in #[command] attribute", name),
+            ));
+        }
+    }
+
+    let return_type = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    // Stateless commands are generic in the value type
+    let (value_type, value_generic, value_bound) = match &state {
+        Some((_, value_type)) => (quote!(#value_type), quote!(), quote!()),
+        None => (
+            quote!(V),
+            quote!(, V),
+            quote!(V: #lc::value::ValueInterface,),
+        ),
+    };
+    let state_parameter = match &state {
+        Some(_) => quote!(state,),
+        None => quote!(),
+    };
+    let state_argument = match &state {
+        Some((name, _)) => quote!(
+            cm.with_state_argument(#lc::command_metadata::ArgumentInfo::any_argument(#name));
+        ),
+        None => quote!(),
+    };
+
+    let arg_types: Vec<&Type> = arguments.iter().map(|(_, t)| t).collect();
+    let arg_idents: Vec<syn::Ident> = arguments
+        .iter()
+        .map(|(n, _)| format_ident!("arg_{}", n))
+        .collect();
+
+    let mut metadata_arguments = Vec::new();
+    for (name, ty) in arguments.iter() {
+        let info = argument_info(name, ty);
+        let mut modifiers = Vec::new();
+        if let Some(a) = attributes.arguments.remove(name) {
+            if let Some(alternatives) = a.alternatives {
+                modifiers.push(quote!(
+                    let mut e = #lc::command_metadata::EnumArgument::new(#name);
+                    #(e.with_alternative(#alternatives);)*
+                    a.with_enum(e);
+                ));
+            }
+            if let Some(default) = a.default {
+                modifiers.push(quote!(a.with_default_value(#default);));
+            }
+            if let Some(label) = a.label {
+                modifiers.push(quote!(a.with_label(#label);));
+            }
+            if let Some(gui) = a.gui {
+                modifiers.push(quote!(
+                    a.with_gui_info({
+                        use #lc::command_metadata::ArgumentGUIInfo::*;
+                        #gui
+                    });
+                ));
+            }
+        }
+        metadata_arguments.push(quote!(
+            if !<#ty as #lc::commands::FromCommandArguments<#ty, ER, E>>::is_injected() {
+                let mut a = #info;
+                #(#modifiers)*
+                cm.with_argument(a);
+            }
+        ));
+    }
+
+    Ok(quote! {
+        #item
+
+        #vis struct #struct_name;
+
+        impl<ER, E #value_generic> #lc::commands::Command<ER, E, #value_type> for #struct_name
+        where
+            ER: #lc::context::EnvRef<E>,
+            E: #lc::context::Environment,
+            #value_bound
+            #(#arg_types: #lc::commands::FromCommandArguments<#arg_types, ER, E>,)*
+            #return_type: #lc::commands::IntoCommandResult<#value_type>,
+        {
+            #[allow(unused_variables)]
+            fn execute(
+                &self,
+                state: &#lc::state::State<#value_type>,
+                arguments: &mut #lc::commands::CommandArguments,
+                context: #lc::context::Context<ER, E>,
+            ) -> ::std::result::Result<#value_type, #lc::error::Error> {
+                use #lc::commands::IntoCommandResult;
+                #(let #arg_idents: #arg_types = arguments.get(&context)?;)*
+                if !arguments.all_parameters_used() {
+                    return Err(#lc::error::Error::new(
+                        #lc::error::ErrorType::TooManyParameters,
+                        format!("Too many parameters: {}; {} excess parameters found", arguments.len(), arguments.excess_parameters()),
+                    )
+                    .with_position(&arguments.parameter_position()));
+                }
+                #fn_name(#state_parameter #(#arg_idents),*)
+                    .into_command_result()
+                    .map_err(|e| arguments.complete_error(e, &context))
+            }
+
+            fn command_metadata(&self) -> Option<#lc::command_metadata::CommandMetadata> {
+                let mut cm = #lc::command_metadata::CommandMetadata::new(#command_name);
+                cm.with_realm(#realm)
+                    .with_namespace(#namespace)
+                    .with_module(module_path!())
+                    .with_doc(#doc);
+                cm.cache = #cache;
+                cm.volatile = #volatile;
+                #state_argument
+                #(#metadata_arguments)*
+                Some(cm)
+            }
+        }
+
+        impl #struct_name {
+            /// Register the command in the command registry
+            #vis fn register<ER, E, V>(
+                cr: &mut #lc::commands::CommandRegistry<ER, E, V>,
+            ) -> ::std::result::Result<&mut #lc::command_metadata::CommandMetadata, #lc::error::Error>
+            where
+                ER: #lc::context::EnvRef<E>,
+                E: #lc::context::Environment,
+                V: #lc::value::ValueInterface,
+                Self: #lc::commands::Command<ER, E, V> + 'static,
+            {
+                cr.register_command(
+                    #lc::command_metadata::CommandKey::new(#realm, #namespace, #command_name),
+                    #struct_name,
+                )
+            }
+        }
+    })
+}
+
+/// Implements `FromParameter` for a type implementing `serde::Deserialize`.
+#[proc_macro_derive(FromParameter)]
+pub fn derive_from_parameter(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote!(
+        impl #impl_generics ::liquers_core::commands::FromParameter<#name #type_generics> for #name #type_generics #where_clause {
+            fn from_parameter(
+                param: &::liquers_core::plan::Parameter,
+            ) -> ::std::result::Result<Self, ::liquers_core::error::Error> {
+                ::liquers_core::commands::deserialize_parameter(param)
+            }
+        }
+    )
+    .into()
+}
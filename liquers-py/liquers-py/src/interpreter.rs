@@ -0,0 +1,140 @@
+use pyo3::prelude::*;
+
+use liquers_core::context::{ArcEnvRef, Environment, SimpleEnvironment};
+use liquers_core::interpreter::{Breakpoint, PlanInterpreter};
+use liquers_core::state::State;
+use liquers_core::value::Value;
+
+type Env = SimpleEnvironment<Value>;
+
+fn to_pyerr(e: liquers_core::error::Error) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyException, _>(e.to_string())
+}
+
+/// Step-by-step evaluation of a query (debugger).
+/// States are returned as serialized data and metadata.
+#[pyclass]
+pub struct Interpreter {
+    envref: ArcEnvRef<Env>,
+    interpreter: PlanInterpreter<ArcEnvRef<Env>, Env>,
+}
+
+impl Interpreter {
+    fn serialize_state(&self, state: &State<Value>) -> PyResult<(Vec<u8>, crate::metadata::Metadata)> {
+        let data = self
+            .envref
+            .0
+            .get_serializer_registry()
+            .serialize_state(state)
+            .map_err(to_pyerr)?;
+        Ok((data, crate::metadata::Metadata((*state.metadata).clone())))
+    }
+}
+
+#[pymethods]
+impl Interpreter {
+    /// Create an interpreter; resources are read from the filesystem store in path (if given)
+    #[new]
+    fn new(path: Option<&str>) -> Self {
+        let mut env: Env = SimpleEnvironment::new();
+        if let Some(path) = path {
+            env.with_store(Box::new(liquers_core::store::FileStore::new(
+                path,
+                &liquers_core::query::Key::new(),
+            )));
+        }
+        let envref = env.to_ref();
+        Interpreter {
+            interpreter: PlanInterpreter::new(envref.clone()),
+            envref,
+        }
+    }
+
+    /// Evaluate the query and return the data and metadata
+    fn evaluate(&mut self, query: &str) -> PyResult<(Vec<u8>, crate::metadata::Metadata)> {
+        let state = self.interpreter.evaluate(query).map_err(to_pyerr)?;
+        self.serialize_state(&state)
+    }
+
+    /// Prepare the plan of the query for the step-by-step evaluation
+    fn with_query(&mut self, query: &str) -> PyResult<()> {
+        self.interpreter.with_query(query).map_err(to_pyerr)?;
+        Ok(())
+    }
+
+    /// Number of steps of the plan
+    fn __len__(&self) -> usize {
+        self.interpreter.len()
+    }
+
+    /// Step of the plan as json
+    fn get_step(&self, i: usize) -> PyResult<String> {
+        let step = self.interpreter.get_step(i).map_err(to_pyerr)?;
+        serde_json::to_string(step)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(e.to_string()))
+    }
+
+    /// Index of the next step to be executed
+    #[getter]
+    fn step_number(&self) -> usize {
+        self.interpreter.step_number()
+    }
+
+    /// True if all the steps of the plan have been executed
+    #[getter]
+    fn is_finished(&self) -> bool {
+        self.interpreter.is_finished()
+    }
+
+    /// Execute the next step and return the resulting data and metadata
+    fn step(&mut self) -> PyResult<(Vec<u8>, crate::metadata::Metadata)> {
+        let state = self.interpreter.step().map_err(to_pyerr)?;
+        self.serialize_state(&state)
+    }
+
+    /// Execute steps until a breakpoint or the end of the plan.
+    /// Returns the index of the step where the evaluation stopped or None if the plan is finished.
+    fn continue_to_breakpoint(&mut self) -> PyResult<Option<usize>> {
+        self.interpreter.continue_to_breakpoint().map_err(to_pyerr)
+    }
+
+    /// Stop before the step with the given index
+    fn add_step_breakpoint(&mut self, step_number: usize) {
+        self.interpreter.with_breakpoint(Breakpoint::Step(step_number));
+    }
+
+    /// Stop before any action with the given name
+    fn add_action_breakpoint(&mut self, action_name: &str) {
+        self.interpreter
+            .with_breakpoint(Breakpoint::Action(action_name.to_string()));
+    }
+
+    fn clear_breakpoints(&mut self) {
+        self.interpreter.clear_breakpoints();
+    }
+
+    /// Return to the state after the given number of steps
+    fn rewind(&mut self, steps: usize) -> PyResult<()> {
+        self.interpreter.rewind(steps).map_err(to_pyerr)
+    }
+
+    /// Change an action parameter (given as json) and rewind to the action
+    fn set_parameter(&mut self, step_number: usize, parameter_index: usize, value: &str) -> PyResult<()> {
+        let value = serde_json::from_str(value)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(e.to_string()))?;
+        self.interpreter
+            .set_parameter(step_number, parameter_index, value)
+            .map_err(to_pyerr)
+    }
+
+    /// Data and metadata after the given number of executed steps (0 is the initial state)
+    fn get_intermediate_state(
+        &self,
+        steps: usize,
+    ) -> PyResult<Option<(Vec<u8>, crate::metadata::Metadata)>> {
+        self.interpreter
+            .get_intermediate_state(steps)
+            .map(|state| self.serialize_state(&state))
+            .transpose()
+    }
+}
@@ -3,6 +3,7 @@ pub mod parse;
 pub mod store;
 pub mod cache;
 pub mod metadata;
+pub mod interpreter;
 use crate::parse::*;
 
 
@@ -28,6 +29,8 @@ fn liquers_py(_py: Python, m: &PyModule) -> PyResult<()> {
 
     m.add_class::<crate::cache::Cache>()?;
     m.add_function(wrap_pyfunction!(crate::cache::memory_cache, m)?)?;
+
+    m.add_class::<crate::interpreter::Interpreter>()?;
    
     Ok(())
 }
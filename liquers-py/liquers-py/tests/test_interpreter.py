@@ -0,0 +1,39 @@
+#!/usr/bin/python
+# -*- coding: utf-8 -*-
+"""
+Unit tests for the LiQueRS interpreter (step-by-step evaluation).
+"""
+import json
+import pytest
+from liquers_py import *
+
+
+class TestInterpreter:
+    def test_evaluate(self, tmp_path):
+        (tmp_path / "a.txt").write_bytes(b"Hello")
+        interpreter = Interpreter(str(tmp_path))
+        data, metadata = interpreter.evaluate("-R/a.txt")
+        assert data == b"Hello"
+
+    def test_step(self, tmp_path):
+        (tmp_path / "a.txt").write_bytes(b"Hello")
+        interpreter = Interpreter(str(tmp_path))
+        interpreter.with_query("-R/a.txt")
+        assert len(interpreter) > 0
+        assert json.loads(interpreter.get_step(0))
+        assert interpreter.step_number == 0
+        data, metadata = interpreter.step()
+        assert data == b"Hello"
+        assert interpreter.is_finished
+        assert interpreter.get_intermediate_state(1)[0] == b"Hello"
+        interpreter.rewind(0)
+        assert interpreter.step_number == 0
+        assert interpreter.get_intermediate_state(1) is None
+        assert interpreter.continue_to_breakpoint() is None
+        assert interpreter.is_finished
+
+    def test_set_parameter_error(self):
+        interpreter = Interpreter()
+        interpreter.with_query("-R/a.txt")
+        with pytest.raises(Exception):
+            interpreter.set_parameter(0, 0, "1")
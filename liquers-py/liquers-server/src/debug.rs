@@ -0,0 +1,218 @@
+//! Interactive step-by-step evaluation of a query: `liquers-server debug <query>`.
+//! Commands are read from the standard input, see [HELP].
+
+use std::io::{BufRead, Write};
+
+use liquers_core::context::SimpleEnvironment;
+use liquers_core::error::Error;
+use liquers_core::interpreter::{Breakpoint, PlanInterpreter};
+use liquers_core::state::State;
+use liquers_core::value::{Value, ValueInterface};
+
+use crate::EnvRef;
+
+pub const HELP: &str = "\
+Commands:
+  plan                        show the steps of the plan
+  step | s                    execute the next step
+  continue | c                execute steps until a breakpoint or the end of the plan
+  break <step>|<action> | b   stop before the step with the given index or before the action
+  clear                       remove all breakpoints
+  rewind <steps> | r          return to the state after the given number of steps
+  set <step> <index> <json>   change an action parameter (and rewind to the action)
+  state [<steps>]             show the state after the given number of steps (the current one by default)
+  help | h                    show this help
+  quit | q                    stop debugging";
+
+/// Debugger session reading commands from the input and writing results to the output
+pub struct Debugger {
+    interpreter: PlanInterpreter<EnvRef, SimpleEnvironment<Value>>,
+}
+
+impl Debugger {
+    pub fn new(envref: EnvRef, query: &str) -> Result<Self, Error> {
+        let mut interpreter = PlanInterpreter::new(envref);
+        interpreter.with_query(query)?;
+        Ok(Debugger { interpreter })
+    }
+
+    /// Execute the commands until the end of the input or the quit command
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> std::io::Result<()> {
+        self.print_position(output)?;
+        for line in input.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "quit" || line == "q" {
+                break;
+            }
+            if let Err(e) = self.execute(line, output)? {
+                writeln!(output, "Error: {}", e)?;
+            }
+            self.print_position(output)?;
+        }
+        Ok(())
+    }
+
+    /// Execute a single command; errors of the evaluation are returned as the inner result
+    pub fn execute<W: Write>(
+        &mut self,
+        line: &str,
+        output: &mut W,
+    ) -> std::io::Result<Result<(), Error>> {
+        let mut words = line.splitn(4, ' ');
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        let pi = &mut self.interpreter;
+        let result = match (command, args.as_slice()) {
+            ("plan", []) => {
+                for i in 0..pi.len() {
+                    let marker = if i == pi.step_number() { ">" } else { " " };
+                    writeln!(output, "{} {:3} {}", marker, i, describe_step(pi.get_step(i)))?;
+                }
+                Ok(())
+            }
+            ("step" | "s", []) => pi.step().map(|state| print_state(&state, output)),
+            ("continue" | "c", []) => pi.continue_to_breakpoint().map(|stop| {
+                if let Some(step_number) = stop {
+                    let _ = writeln!(output, "Breakpoint before step {}", step_number);
+                }
+                if let Some(state) = pi.get_state() {
+                    print_state(&state, output);
+                }
+            }),
+            ("break" | "b", [target]) => {
+                let breakpoint = target
+                    .parse()
+                    .map(Breakpoint::Step)
+                    .unwrap_or_else(|_| Breakpoint::Action(target.to_string()));
+                pi.with_breakpoint(breakpoint);
+                Ok(())
+            }
+            ("clear", []) => {
+                pi.clear_breakpoints();
+                Ok(())
+            }
+            ("rewind" | "r", [steps]) => parse_number(steps).and_then(|steps| pi.rewind(steps)),
+            ("set", [step, index, json]) => parse_number(step).and_then(|step| {
+                let index = parse_number(index)?;
+                let value = serde_json::from_str(json).map_err(|e| {
+                    Error::general_error(format!("Invalid parameter value {}: {}", json, e))
+                })?;
+                pi.set_parameter(step, index, value)
+            }),
+            ("state", []) => {
+                let steps = pi.step_number();
+                show_state(pi, steps, output)
+            }
+            ("state", [steps]) => parse_number(steps).and_then(|steps| show_state(pi, steps, output)),
+            ("help" | "h", []) => {
+                writeln!(output, "{}", HELP)?;
+                Ok(())
+            }
+            _ => Err(Error::general_error(format!(
+                "Unknown command '{}', type 'help' for the list of commands",
+                line
+            ))),
+        };
+        Ok(result)
+    }
+
+    fn print_position<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        let pi = &self.interpreter;
+        if pi.is_finished() {
+            writeln!(output, "[finished]")
+        } else {
+            writeln!(
+                output,
+                "[{}/{}] {}",
+                pi.step_number(),
+                pi.len(),
+                describe_step(pi.get_step(pi.step_number()))
+            )
+        }
+    }
+}
+
+fn describe_step(step: Result<&liquers_core::plan::Step, Error>) -> String {
+    match step {
+        Ok(step) => format!("{:?}", step),
+        Err(e) => e.to_string(),
+    }
+}
+
+fn parse_number(text: &str) -> Result<usize, Error> {
+    text.parse()
+        .map_err(|_| Error::general_error(format!("Number expected instead of '{}'", text)))
+}
+
+fn show_state<W: Write>(
+    pi: &PlanInterpreter<EnvRef, SimpleEnvironment<Value>>,
+    steps: usize,
+    output: &mut W,
+) -> Result<(), Error> {
+    let state = pi.get_intermediate_state(steps).ok_or(Error::general_error(format!(
+        "State after {} steps is not available",
+        steps
+    )))?;
+    print_state(&state, output);
+    Ok(())
+}
+
+fn print_state<W: Write>(state: &State<Value>, output: &mut W) {
+    let text = state
+        .data
+        .try_into_string()
+        .unwrap_or_else(|_| format!("{:?}", state.data));
+    let _ = writeln!(output, "{}", text);
+}
+
+/// Run the debugger on the standard input and output
+pub fn debug(envref: EnvRef, query: &str) -> Result<(), Error> {
+    let mut debugger = Debugger::new(envref, query)?;
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    debugger
+        .run(stdin.lock(), &mut stdout)
+        .map_err(|e| Error::general_error(format!("Debugger I/O error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liquers_core::command_metadata::ArgumentInfo;
+    use liquers_core::commands::{Command1, Command2};
+    use liquers_core::context::Environment;
+
+    fn envref() -> EnvRef {
+        let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+        {
+            let cr = env.get_mut_command_executor();
+            cr.register_command("hello", Command1::from(|_state: &State<Value>| "Hello"))
+                .unwrap();
+            cr.register_command(
+                "greet",
+                Command2::from(|state: &State<Value>, who: String| -> Result<String, Error> {
+                    Ok(format!("{}, {}!", state.data.try_into_string()?, who))
+                }),
+            )
+            .unwrap()
+            .with_argument(ArgumentInfo::string_argument("who"));
+        }
+        env.to_ref()
+    }
+
+    #[test]
+    fn test_debugger_session() {
+        let mut debugger = Debugger::new(envref(), "hello/greet-world").unwrap();
+        let input = "plan\nstep\nset 1 0 \"there\"\ncontinue\nrewind 1\nstate\nfoo\nq\nstep\n";
+        let mut output = Vec::new();
+        debugger.run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Hello, there!"));
+        assert!(output.contains("Unknown command 'foo'"));
+        assert!(output.lines().last().unwrap().starts_with("[1/2]"));
+    }
+}
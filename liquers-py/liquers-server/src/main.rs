@@ -7,10 +7,20 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
+mod debug;
+
 use axum::extract::{Path, State};
+use liquers_core::cache::MemoryCache;
+use liquers_core::context::{ArcEnvRef, Environment, SimpleEnvironment};
+use liquers_core::error::{Error, ErrorType};
+use liquers_core::interpreter::PlanInterpreter;
 use liquers_core::parse::parse_key;
 use liquers_core::query::Key;
 use liquers_core::store::{FileStore, Store};
+use liquers_core::value::Value;
+
+/// Environment shared by the query handlers
+type EnvRef = ArcEnvRef<SimpleEnvironment<Value>>;
 
 #[derive(Serialize, Deserialize, Debug)]
 enum StatusCode {
@@ -33,8 +43,46 @@ pub struct ServiceStatus<T> {
     result: T,
 }
 
-async fn evaluate_query(Path(query): Path<String>) -> impl axum::response::IntoResponse {
-    format!("Hello, {}!", query)
+/// HTTP status code of an error response
+fn error_status(e: &Error) -> axum::http::StatusCode {
+    match e.error_type {
+        ErrorType::KeyNotFound => axum::http::StatusCode::NOT_FOUND,
+        ErrorType::ParseError | ErrorType::ParameterError => axum::http::StatusCode::BAD_REQUEST,
+        ErrorType::PermissionDenied => axum::http::StatusCode::FORBIDDEN,
+        _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Evaluate the query and return the serialized result.
+/// The format and the content type are given by the filename at the end of the query,
+/// e.g. `/liquer/q/<query>/result.json`; the default format of the value is used otherwise.
+/// The evaluation is blocking, so it runs on the blocking thread pool of tokio.
+async fn evaluate_query(
+    State(envref): State<EnvRef>,
+    Path(query): Path<String>,
+) -> impl axum::response::IntoResponse {
+    let registry = envref.0.get_serializer_registry();
+    let result = tokio::task::spawn_blocking(move || PlanInterpreter::new(envref).evaluate(&query))
+        .await
+        .unwrap_or_else(|e| Err(Error::general_error(format!("Evaluation task failed: {}", e))))
+        .and_then(|state| {
+            Ok((
+                registry.state_media_type(&state),
+                registry.serialize_state(&state)?,
+            ))
+        });
+    match result {
+        Ok((media_type, data)) => (
+            axum::http::StatusCode::OK,
+            [(header::CONTENT_TYPE, media_type)],
+            data,
+        ),
+        Err(e) => (
+            error_status(&e),
+            [(header::CONTENT_TYPE, "text/plain".to_owned())],
+            format!("Error evaluating query: {}", e).into(),
+        ),
+    }
 }
 
 async fn submit_query(Path(query): Path<String>) -> Json<SimpleStatus> {
@@ -50,18 +98,26 @@ async fn store_get<S: Store>(
     State(store): State<Arc<RwLock<S>>>,
     Path(query): Path<String>,
 ) -> impl axum::response::IntoResponse {
-
-    let key = parse_key(query).unwrap();
+    let key = match parse_key(query) {
+        Ok(key) => key,
+        Err(e) => {
+            return (
+                error_status(&e),
+                [(header::CONTENT_TYPE, "text/plain".to_owned())],
+                format!("Error parsing key: {}", e).into(),
+            )
+        }
+    };
     match store.read(){
         Ok(store) => {
-            match store.get(&key){
+            match store.get(&key).map_err(Error::from){
                 Ok((data, metadata)) => (
                     axum::http::StatusCode::OK,
                     [(header::CONTENT_TYPE, metadata.get_media_type())],
                     data,
                 ),
                 Err(e) => (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    error_status(&e),
                     [(header::CONTENT_TYPE, "text/plain".to_owned())],
                     format!("Error reading store: {}", e).into(),
                 ),
@@ -353,20 +409,51 @@ def store_makedir(query):
         )
 */
 
+/// Environment of the server, evaluating queries on the store in the given directory.
+/// The file store refuses keys pointing outside of the directory.
+fn create_environment(path: &str) -> EnvRef {
+    let mut env: SimpleEnvironment<Value> = SimpleEnvironment::new();
+    env.with_store(Box::new(FileStore::new(path, &Key::new())))
+        .with_cache(Box::new(MemoryCache::new()));
+    env.to_ref()
+}
+
+/// Start the server, or debug a query with `liquers-server debug <query>`
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        None => serve(),
+        Some("debug") if args.len() == 3 => {
+            if let Err(e) = debug::debug(create_environment("."), &args[2]) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("Usage: {} [debug <query>]\n\n{}", args[0], debug::HELP);
+            std::process::exit(2);
+        }
+    }
+}
+
 #[tokio::main]
-async fn main() {
+async fn serve() {
     // build our application with a single route
 
     let shared_state = Arc::new(RwLock::new(FileStore::new(".", &Key::new())));
 
+    let query_routes = Router::new()
+        .route("/liquer/q/*query", get(evaluate_query))
+        .with_state(create_environment("."));
+
     let app = Router::new()
         .route("/", get(|| async { "Hello, World!" }))
-        .route("/liquer/q/*query", get(evaluate_query))
         .route("/liquer/submit/*query", get(submit_query))
         .route("/liquer/api/store/data/*query", get(store_get))
 //        .route("/liquer/web/*query", get(web_store_get))
 //        .route("/liquer/store/upload/*query", get(store_upload_get))
-        .with_state(shared_state);
+        .with_state(shared_state)
+        .merge(query_routes);
 
     // run it with hyper on localhost:3000
     axum::Server::bind(&"0.0.0.0:3000".parse().unwrap())
@@ -374,3 +461,39 @@ async fn main() {
         .await
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_query_outside_of_store() {
+        use axum::response::IntoResponse;
+        let dir = std::env::temp_dir().join(format!("liquers-server-{}", std::process::id()));
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+        std::fs::write(root.join("public.txt"), b"public").unwrap();
+        let envref = create_environment(root.to_str().unwrap());
+
+        let response = evaluate_query(State(envref.clone()), Path("-R/public.txt".to_string()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let response = evaluate_query(State(envref), Path("-R/../secret.txt".to_string()))
+            .await
+            .into_response();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_error_status() {
+        let status = |error_type| error_status(&Error::new(error_type, "error".to_string()));
+        assert_eq!(status(ErrorType::KeyNotFound), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(status(ErrorType::ParseError), axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(status(ErrorType::ParameterError), axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(status(ErrorType::PermissionDenied), axum::http::StatusCode::FORBIDDEN);
+        assert_eq!(status(ErrorType::General), axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}